@@ -1,36 +1,945 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+    InstanceType, ObjectValidation, RootSchema, Schema, SchemaObject, SubschemaValidation,
+};
+use schemars::JsonSchema;
+use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 pub type ShellScript = String;
 
-use crate::combination::KeyCombination;
+use crate::combination::{Combination, KeyCombination, KeySchema};
+use crate::config::expand_home;
+use crate::placement::string_enum_schema;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// The argument of actions that take none.
+///
+/// It is written as `true`, which every configuration format can express,
+/// and read from null, `true` or an empty map, so that `reload:`,
+/// `reload: true` and `"reload": {}` are all accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NoArgs;
+
+impl Serialize for NoArgs {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(true)
+    }
+}
+
+impl<'de> Deserialize<'de> for NoArgs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NoArgsVisitor)
+    }
+}
+
+struct NoArgsVisitor;
+
+impl<'de> Visitor<'de> for NoArgsVisitor {
+    type Value = NoArgs;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nothing, `true` or `{{}}`")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<NoArgs, E> {
+        Ok(NoArgs)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<NoArgs, E> {
+        Ok(NoArgs)
+    }
+
+    // `false` would read as not doing the action
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<NoArgs, E> {
+        if value {
+            Ok(NoArgs)
+        } else {
+            Err(de::Error::invalid_value(de::Unexpected::Bool(value), &self))
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<NoArgs, A::Error> {
+        match access.next_key::<de::IgnoredAny>()? {
+            None => Ok(NoArgs),
+            Some(_) => Err(de::Error::invalid_value(de::Unexpected::Map, &self)),
+        }
+    }
+}
+
+impl JsonSchema for NoArgs {
+    fn schema_name() -> String {
+        "NoArgs".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let empty_map = SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            object: Some(Box::new(ObjectValidation {
+                max_properties: Some(0),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let values = vec![serde_json::Value::Null, serde_json::Value::Bool(true)];
+
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![
+                    SchemaObject {
+                        enum_values: Some(values),
+                        ..Default::default()
+                    }
+                    .into(),
+                    empty_map.into(),
+                ]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
 pub struct ActionDesc {
-    #[serde(default)]
+    #[schemars(default)]
     #[serde(skip_serializing_if = "String::is_empty")]
     pub title: String,
+    /// Parse the title as Pango markup. When set on a `map` node, this is
+    /// also the default for all the nodes below it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markup: Option<bool>,
+    /// Image to show beside the title
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<PathBuf>,
+    /// Variables added to the environment of the commands of this node, and
+    /// of the nodes below it
+    #[schemars(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Working directory of the commands of this node, and of the nodes
+    /// below it. Relative paths are taken from the directory of the nodes above.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
     #[serde(flatten)]
     pub action: Action,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Action {
-    Execute(ShellScript),
-    Reload(()),
-    Die(()),
+    Execute(Execute),
+    Reload(NoArgs),
+    Die(NoArgs),
+    Show(Show),
 
-    List(Vec<Op>),
-    Map(HashMap<KeyCombination, ActionDesc>),
+    List(Vec<Step>),
+    Map(#[schemars(schema_with = "map_schema")] HashMap<KeyCombination, ActionDesc>),
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Op {
-    Execute(ShellScript),
-    Reload(()),
-    Die(()),
+    Execute(Execute),
+    Reload(NoArgs),
+    Die(NoArgs),
+    Show(Show),
+}
+
+// Written as a single-entry map, which is what the derived implementation
+// produces in YAML and JSON, but also works in TOML where enum variants with
+// data are not supported.
+impl Serialize for Op {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            Op::Execute(e) => map.serialize_entry("execute", e)?,
+            Op::Reload(r) => map.serialize_entry("reload", r)?,
+            Op::Die(d) => map.serialize_entry("die", d)?,
+            Op::Show(s) => map.serialize_entry("show", s)?,
+        }
+        map.end()
+    }
+}
+
+impl Op {
+    /// The operation run with the environment, working directory and
+    /// variables of a context.
+    pub fn resolve(&self, context: &Context) -> Op {
+        match self {
+            Op::Execute(e) => Op::Execute(e.resolve(context)),
+            Op::Show(s) => Op::Show(Show {
+                execute: s.execute.as_ref().map(|e| e.resolve(context)),
+                text: s.text.as_ref().map(|text| context.substitute(text)),
+                duration: s.duration,
+            }),
+            op => op.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Op::Execute(e) => write!(f, "{}", e.to_shell()),
+            Op::Reload(_) => write!(f, "reload"),
+            Op::Die(_) => write!(f, "die"),
+            Op::Show(Show {
+                execute: Some(e), ..
+            }) => write!(f, "show the output of {}", e.to_shell()),
+            Op::Show(Show { text, .. }) => write!(f, "show {:?}", text.as_deref().unwrap_or("")),
+        }
+    }
+}
+
+/// What a `show` action displays: the output of a command, or a text.
+#[derive(Debug, Serialize, Clone, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Show {
+    /// Command whose output is shown once it has exited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execute: Option<Execute>,
+    /// Text shown as it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Milliseconds during which it is shown, unless a key is pressed, or 0
+    /// to wait for a key. By default, as long as errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+/// A step of a `list`, started once the previous one has finished.
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct Step {
+    #[serde(flatten)]
+    pub op: Op,
+    /// Milliseconds after which the command is stopped, which is a failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// What happens when the command fails: `stop` the list, `continue` with
+    /// the next step, or run another operation instead of the rest
+    #[schemars(default)]
+    #[serde(skip_serializing_if = "OnFailure::is_stop")]
+    pub on_failure: OnFailure,
+}
+
+impl Step {
+    /// The step run with the environment and working directory of a context.
+    pub fn resolve(&self, context: &Context) -> Step {
+        let on_failure = match &self.on_failure {
+            OnFailure::Run(op) => OnFailure::Run(op.resolve(context)),
+            on_failure => on_failure.clone(),
+        };
+
+        Step {
+            op: self.op.resolve(context),
+            timeout: self.timeout,
+            on_failure,
+        }
+    }
+}
+
+impl From<Op> for Step {
+    fn from(op: Op) -> Step {
+        Step {
+            op,
+            timeout: None,
+            on_failure: OnFailure::Stop,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OnFailure {
+    #[default]
+    Stop,
+    Continue,
+    Run(Op),
+}
+
+impl OnFailure {
+    fn is_stop(&self) -> bool {
+        *self == OnFailure::Stop
+    }
+}
+
+impl Serialize for OnFailure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            OnFailure::Stop => serializer.serialize_str("stop"),
+            OnFailure::Continue => serializer.serialize_str("continue"),
+            OnFailure::Run(op) => op.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OnFailure {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(OnFailureVisitor)
+    }
+}
+
+struct OnFailureVisitor;
+
+impl<'de> Visitor<'de> for OnFailureVisitor {
+    type Value = OnFailure;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`stop`, `continue` or an operation")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<OnFailure, E> {
+        match value {
+            "stop" => Ok(OnFailure::Stop),
+            "continue" => Ok(OnFailure::Continue),
+            _ => Err(de::Error::invalid_value(de::Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, access: A) -> Result<OnFailure, A::Error> {
+        Op::deserialize(de::value::MapAccessDeserializer::new(access)).map(OnFailure::Run)
+    }
+}
+
+impl JsonSchema for OnFailure {
+    fn schema_name() -> String {
+        "OnFailure".to_owned()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![
+                    string_enum_schema(vec!["stop".to_owned(), "continue".to_owned()]),
+                    gen.subschema_for::<Op>(),
+                ]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// What an `execute` action runs: a script for the shell, or a program with
+/// its arguments, environment and working directory.
+#[derive(Debug, Serialize, Clone, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum Execute {
+    Script(ShellScript),
+    Command(Command),
+}
+
+/// The map form of `execute`. Either `argv` or `script` is given.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Command {
+    /// Program and arguments, run without a shell
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub argv: Vec<String>,
+    /// Script run by the shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<ShellScript>,
+    /// Variables added to the environment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Working directory, relative to the one of the nodes above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Shell running `script` with `-c`, instead of the `shell` setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Log the output and report failures, instead of the `capture` setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture: Option<bool>,
+}
+
+impl<'de> Deserialize<'de> for Execute {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ExecuteVisitor)
+    }
+}
+
+struct ExecuteVisitor;
+
+impl<'de> Visitor<'de> for ExecuteVisitor {
+    type Value = Execute;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a shell script, or a map with `argv` or `script`")
+    }
+
+    fn visit_str<E: de::Error>(self, script: &str) -> Result<Execute, E> {
+        Ok(Execute::Script(script.to_owned()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, access: A) -> Result<Execute, A::Error> {
+        let command = Command::deserialize(de::value::MapAccessDeserializer::new(access))?;
+
+        let error = match (command.argv.is_empty(), &command.script, &command.shell) {
+            (true, None, _) => "missing `argv` or `script`",
+            (false, Some(_), _) => "`argv` and `script` can't both be given",
+            (false, None, Some(_)) => "`shell` only runs a `script`, not `argv`",
+            _ => return Ok(Execute::Command(command)),
+        };
+        Err(de::Error::custom(error))
+    }
+}
+
+/// Quote a word for the shell, unless it needs none.
+pub(crate) fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// The working directory below `base` given as `dir`, either absolute or
+/// relative to `base`.
+fn join_cwd(base: Option<&Path>, dir: Option<&Path>) -> Option<PathBuf> {
+    match (base, dir.map(expand_home)) {
+        (Some(base), Some(dir)) => Some(base.join(dir)),
+        (base, dir) => dir.or_else(|| base.map(Path::to_owned)),
+    }
+}
+
+impl Execute {
+    /// The command with the environment and working directory inherited from
+    /// the nodes above it, its own taking precedence, and the placeholders of
+    /// its `argv`, `env` and `cwd` replaced by the variables of the context.
+    /// Scripts are left as they are: values such as window titles are not
+    /// safe in a shell, which reads them from the environment instead.
+    pub fn resolve(&self, context: &Context) -> Execute {
+        if context.env.is_empty() && context.cwd.is_none() && context.vars.is_empty() {
+            return self.clone();
+        }
+
+        let mut command = match self {
+            Execute::Script(script) => Command {
+                script: Some(script.clone()),
+                ..Default::default()
+            },
+            Execute::Command(command) => command.clone(),
+        };
+        let mut env = context.env.clone();
+        env.append(&mut command.env);
+        command.env = env;
+        command.cwd = join_cwd(context.cwd.as_deref(), command.cwd.as_deref());
+
+        command.argv = command.argv.iter().map(|arg| context.substitute(arg)).collect();
+        for value in command.env.values_mut() {
+            *value = context.substitute(value);
+        }
+        command.cwd = command
+            .cwd
+            .map(|cwd| PathBuf::from(context.substitute(&cwd.to_string_lossy())));
+
+        Execute::Command(command)
+    }
+
+    /// A process running the command, scripts being run by `shell` unless the
+    /// command names another.
+    pub fn process(&self, shell: &str) -> std::process::Command {
+        let command = match self {
+            Execute::Script(script) => {
+                let mut process = std::process::Command::new(shell);
+                process.arg("-c").arg(script);
+                return process;
+            }
+            Execute::Command(command) => command,
+        };
+
+        let mut process = match (&command.script, command.argv.split_first()) {
+            (Some(script), _) => {
+                let shell = command.shell.as_deref().unwrap_or(shell);
+                let mut process = std::process::Command::new(shell);
+                process.arg("-c").arg(script);
+                process
+            }
+            (None, Some((program, args))) => {
+                let mut process = std::process::Command::new(program);
+                process.args(args);
+                process
+            }
+            (None, None) => std::process::Command::new("true"),
+        };
+        process.envs(&command.env);
+        if let Some(cwd) = &command.cwd {
+            process.current_dir(expand_home(cwd));
+        }
+
+        process
+    }
+
+    /// The script run by the shell, if any.
+    pub fn script(&self) -> Option<&str> {
+        match self {
+            Execute::Script(script) => Some(script),
+            Execute::Command(command) => command.script.as_deref(),
+        }
+    }
+
+    /// Whether the output is captured, when the command says.
+    pub fn capture(&self) -> Option<bool> {
+        match self {
+            Execute::Script(_) => None,
+            Execute::Command(command) => command.capture,
+        }
+    }
+
+    /// The command as the configuration writes it, its script or its quoted
+    /// program and arguments, for messages.
+    pub fn describe(&self) -> String {
+        match self {
+            Execute::Command(command) if command.script.is_none() => {
+                let words: Vec<String> = command.argv.iter().map(|arg| shell_quote(arg)).collect();
+                words.join(" ")
+            }
+            _ => self.script().unwrap_or_default().to_owned(),
+        }
+    }
+
+    /// An equivalent shell command line, for the exports.
+    pub fn to_shell(&self) -> String {
+        let command = match self {
+            Execute::Script(script) => return script.clone(),
+            Execute::Command(command) => command,
+        };
+
+        let mut words: Vec<String> = command
+            .env
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, shell_quote(value)))
+            .collect();
+        match (&command.script, &command.shell) {
+            (Some(script), None) if words.is_empty() => words.push(script.clone()),
+            (Some(script), shell) => {
+                words.push(shell_quote(shell.as_deref().unwrap_or("sh")));
+                words.push("-c".to_owned());
+                words.push(shell_quote(script));
+            }
+            (None, _) => words.extend(command.argv.iter().map(|arg| shell_quote(arg))),
+        }
+
+        let line = words.join(" ");
+        match &command.cwd {
+            Some(cwd) => format!("cd {} && {}", shell_quote(&cwd.to_string_lossy()), line),
+            None => line,
+        }
+    }
+}
+
+/// Names of the variables describing how a binding was triggered
+pub(crate) const VARIABLES: &[&str] = &[
+    "path",
+    "key",
+    "title",
+    "window",
+    "window_class",
+    "window_instance",
+    "window_pid",
+    "window_title",
+];
+
+/// The variables written as placeholders in `text`, which is not one where
+/// they are replaced.
+pub(crate) fn placeholders(text: &str) -> Vec<&'static str> {
+    VARIABLES
+        .iter()
+        .copied()
+        .filter(|name| {
+            let placeholder = format!("{{{}}}", name);
+            text.match_indices(&placeholder).any(|(i, _)| !text[..i].ends_with('$'))
+        })
+        .collect()
+}
+
+/// The environment and working directory that `map` nodes give the commands
+/// below them, and the variables describing how a binding was triggered.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context {
+    pub env: BTreeMap<String, String>,
+    pub cwd: Option<PathBuf>,
+    /// Values of the `{name}` placeholders
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Context {
+    /// The context of the commands of a node, and of the nodes below it.
+    pub fn enter(&self, desc: &ActionDesc) -> Context {
+        let mut env = self.env.clone();
+        env.extend(desc.env.clone());
+        Context {
+            env,
+            cwd: join_cwd(self.cwd.as_deref(), desc.cwd.as_deref()),
+            vars: self.vars.clone(),
+        }
+    }
+
+    /// Give commands a variable, as the `{name}` placeholder and as
+    /// `KEYTREE_NAME` in their environment.
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        let variable = format!("KEYTREE_{}", name.to_uppercase());
+        self.env.insert(variable, value.to_owned());
+        self.vars.insert(name.to_owned(), value.to_owned());
+    }
+
+    /// Replace the placeholders of variables in `text` by their values. Other
+    /// braces, and the shell's `${name}`, are kept.
+    pub fn substitute(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            let (before, after) = (&rest[..start], &rest[start + 1..]);
+            result.push_str(before);
+
+            let name = after.find('}').map(|end| &after[..end]);
+            let name = name.filter(|_| !before.ends_with('$'));
+            match (name, name.and_then(|name| self.vars.get(name))) {
+                (Some(name), Some(value)) => {
+                    result.push_str(value);
+                    rest = &after[name.len() + 1..];
+                }
+                _ => {
+                    result.push('{');
+                    rest = after;
+                }
+            }
+        }
+        result.push_str(rest);
+
+        result
+    }
+}
+
+pub(crate) const FIELDS: &[&str] = &[
+    "title", "markup", "icon", "env", "cwd", "execute", "reload", "die", "show", "list", "map",
+];
+
+/// Fields of a step of a `list`
+const STEP_FIELDS: &[&str] = &["execute", "reload", "die", "show", "timeout", "on_failure"];
+
+/// Fields of a `show` action
+const SHOW_FIELDS: &[&str] = &["execute", "text", "duration"];
+
+// Read without `flatten`, which buffers the node and loses the position of
+// errors inside it.
+impl<'de> Deserialize<'de> for ActionDesc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ActionDescVisitor)
+    }
+}
+
+struct ActionDescVisitor;
+
+impl<'de> Visitor<'de> for ActionDescVisitor {
+    type Value = ActionDesc;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a binding")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<ActionDesc, A::Error> {
+        let mut title = None;
+        let mut markup = None;
+        let mut icon = None;
+        let mut env = None;
+        let mut cwd = None;
+        let mut action = None;
+
+        while let Some(key) = access.next_key_seed(Fields(FIELDS))? {
+            let value = match key {
+                "title" => {
+                    title = Some(access.next_value()?);
+                    continue;
+                }
+                "markup" => {
+                    markup = access.next_value()?;
+                    continue;
+                }
+                "icon" => {
+                    icon = access.next_value()?;
+                    continue;
+                }
+                "env" => {
+                    env = Some(access.next_value()?);
+                    continue;
+                }
+                "cwd" => {
+                    cwd = access.next_value()?;
+                    continue;
+                }
+                "execute" => Action::Execute(access.next_value()?),
+                "reload" => Action::Reload(access.next_value()?),
+                "die" => Action::Die(access.next_value()?),
+                "show" => Action::Show(access.next_value()?),
+                "list" => Action::List(access.next_value()?),
+                "map" => Action::Map(access.next_value::<Tree>()?.0),
+                _ => unreachable!(),
+            };
+
+            if action.replace(value).is_some() {
+                return Err(de::Error::custom(format!(
+                    "more than one action, `{}` is extra",
+                    key
+                )));
+            }
+        }
+
+        let action = action.ok_or_else(|| {
+            de::Error::custom(
+                "missing action, expected one of `execute`, `reload`, `die`, `show`, `list` or `map`",
+            )
+        })?;
+
+        Ok(ActionDesc {
+            title: title.unwrap_or_default(),
+            markup,
+            icon,
+            env: env.unwrap_or_default(),
+            cwd,
+            action,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Step {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(StepVisitor)
+    }
+}
+
+struct StepVisitor;
+
+impl<'de> Visitor<'de> for StepVisitor {
+    type Value = Step;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a step")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Step, A::Error> {
+        let mut op = None;
+        let mut timeout = None;
+        let mut on_failure = None;
+
+        while let Some(key) = access.next_key_seed(Fields(STEP_FIELDS))? {
+            let value = match key {
+                "timeout" => {
+                    timeout = access.next_value()?;
+                    continue;
+                }
+                "on_failure" => {
+                    on_failure = Some(access.next_value()?);
+                    continue;
+                }
+                "execute" => Op::Execute(access.next_value()?),
+                "reload" => Op::Reload(access.next_value()?),
+                "die" => Op::Die(access.next_value()?),
+                "show" => Op::Show(access.next_value()?),
+                _ => unreachable!(),
+            };
+
+            if op.replace(value).is_some() {
+                return Err(de::Error::custom(format!(
+                    "more than one operation, `{}` is extra",
+                    key
+                )));
+            }
+        }
+
+        let op = op.ok_or_else(|| {
+            de::Error::custom(
+                "missing operation, expected one of `execute`, `reload`, `die` or `show`",
+            )
+        })?;
+
+        Ok(Step {
+            op,
+            timeout,
+            on_failure: on_failure.unwrap_or_default(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Show {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ShowVisitor)
+    }
+}
+
+struct ShowVisitor;
+
+impl<'de> Visitor<'de> for ShowVisitor {
+    type Value = Show;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map with `execute` or `text`")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Show, A::Error> {
+        let mut show = Show {
+            execute: None,
+            text: None,
+            duration: None,
+        };
+
+        while let Some(key) = access.next_key_seed(Fields(SHOW_FIELDS))? {
+            match key {
+                "execute" => show.execute = Some(access.next_value()?),
+                "text" => show.text = Some(access.next_value()?),
+                "duration" => show.duration = access.next_value()?,
+                _ => unreachable!(),
+            }
+        }
+
+        match (&show.execute, &show.text) {
+            (None, None) => Err(de::Error::custom("missing `execute` or `text`")),
+            (Some(_), Some(_)) => Err(de::Error::custom("`execute` and `text` can't both be given")),
+            _ => Ok(show),
+        }
+    }
+}
+
+/// The names of the fields of a node. Names are checked as they are read, so
+/// that errors point at them.
+struct Fields(&'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for Fields {
+    type Value = &'static str;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Fields {
+    type Value = &'static str;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<&'static str, E> {
+        match self.0.iter().find(|field| **field == name) {
+            Some(field) => Ok(field),
+            None => Err(de::Error::unknown_field(name, self.0)),
+        }
+    }
+}
+
+/// A key of a `map`: a combination, or a comma-separated list of aliases.
+struct Key(KeyCombination);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = Key;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a key combination")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> Result<Key, E> {
+        match key.split(',').find(|alias| Combination::parse(alias).is_err()) {
+            Some(alias) => Err(de::Error::custom(format!("unknown key {:?}", alias))),
+            None => Ok(Key(key.to_owned())),
+        }
+    }
+}
+
+/// The bindings of a `map`. Null entries, which delete nodes read from other files, are left out.
+struct Tree(HashMap<KeyCombination, ActionDesc>);
+
+impl<'de> Deserialize<'de> for Tree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(TreeVisitor)
+    }
+}
+
+struct TreeVisitor;
+
+impl<'de> Visitor<'de> for TreeVisitor {
+    type Value = Tree;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of key combinations to bindings")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Tree, A::Error> {
+        let mut map = HashMap::new();
+
+        while let Some(Key(key)) = access.next_key()? {
+            if let Some(desc) = access.next_value::<Option<ActionDesc>>()? {
+                map.insert(key, desc);
+            }
+        }
+
+        Ok(Tree(map))
+    }
+}
+
+pub(crate) fn deserialize_map<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<KeyCombination, ActionDesc>, D::Error> {
+    Tree::deserialize(deserializer).map(|tree| tree.0)
+}
+
+/// Schema of a `map`, where null entries delete nodes of other files.
+pub(crate) fn map_schema(gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            property_names: Some(Box::new(gen.subschema_for::<KeySchema>())),
+            additional_properties: Some(Box::new(gen.subschema_for::<Option<ActionDesc>>())),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Correct the derived schema of a node whose enum field is flattened beside
+/// the others: each variant must allow the other fields, and it is the node
+/// that allows no unknown ones.
+fn fix_flattened(root: &mut RootSchema, name: &str, fields: &[&str]) {
+    let node = match root.definitions.get_mut(name) {
+        Some(Schema::Object(node)) => node,
+        _ => return,
+    };
+
+    for variant in node.subschemas().one_of.iter_mut().flatten() {
+        if let Schema::Object(variant) = variant {
+            variant.object().additional_properties = None;
+        }
+    }
+
+    node.object().property_names = Some(Box::new(
+        SchemaObject {
+            enum_values: Some(fields.iter().map(|field| (*field).into()).collect()),
+            ..Default::default()
+        }
+        .into(),
+    ));
+}
+
+/// Correct the derived schema of bindings and of the steps of lists.
+pub(crate) fn fix_schema(root: &mut RootSchema) {
+    fix_flattened(root, &ActionDesc::schema_name(), FIELDS);
+    fix_flattened(root, &Step::schema_name(), STEP_FIELDS);
 }
 
 impl Action {
@@ -41,13 +950,15 @@ impl Action {
         }
     }
 
-    pub fn to_op_list(&self) -> Vec<Op> {
+    /// What the action runs, as the steps of a list.
+    pub fn to_steps(&self) -> Vec<Step> {
         let mut v = vec![];
 
         match self {
-            Action::Execute(e) => v.push(Op::Execute(e.clone())),
-            Action::Reload(r) => v.push(Op::Reload(r.clone())),
-            Action::Die(d) => v.push(Op::Die(d.clone())),
+            Action::Execute(e) => v.push(Op::Execute(e.clone()).into()),
+            Action::Reload(r) => v.push(Op::Reload(*r).into()),
+            Action::Die(d) => v.push(Op::Die(*d).into()),
+            Action::Show(s) => v.push(Op::Show(s.clone()).into()),
             Action::List(l) => v = l.clone(),
             Action::Map(_) => {}
         }
@@ -55,3 +966,140 @@ impl Action {
         v
     }
 }
+
+/// Find the binding of a combination in a map, either under its own key or as
+/// part of a comma-separated alias list such as `a,b`.
+pub fn lookup<'a>(
+    map: &'a HashMap<KeyCombination, ActionDesc>,
+    combination: &str,
+) -> Option<(&'a KeyCombination, &'a ActionDesc)> {
+    if let Some(entry) = map.get_key_value(combination) {
+        return Some(entry);
+    }
+
+    map.iter()
+        .find(|(key, _)| key.split(',').any(|alias| alias == combination))
+}
+
+/// A leaf running `command` as a script, titled with the command.
+#[cfg(test)]
+pub(crate) fn leaf(command: &str) -> ActionDesc {
+    ActionDesc {
+        title: command.to_owned(),
+        markup: None,
+        icon: None,
+        env: BTreeMap::new(),
+        cwd: None,
+        action: Action::Execute(Execute::Script(command.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(yaml: &str) -> Result<Action, serde_yaml::Error> {
+        serde_yaml::from_str::<ActionDesc>(yaml).map(|desc| desc.action)
+    }
+
+    #[test]
+    fn no_args_accepted() {
+        for yaml in &["reload:", "reload: ~", "reload: true", "reload: {}"] {
+            assert!(
+                matches!(action(yaml), Ok(Action::Reload(NoArgs))),
+                "{}",
+                yaml
+            );
+        }
+        let json: ActionDesc = serde_json::from_str(r#"{"die": {}}"#).unwrap();
+        assert!(matches!(json.action, Action::Die(NoArgs)));
+        let toml: ActionDesc = toml::from_str("die = true").unwrap();
+        assert!(matches!(toml.action, Action::Die(NoArgs)));
+    }
+
+    #[test]
+    fn no_args_rejected() {
+        for yaml in &[
+            "die: false",
+            "reload: \"no\"",
+            "reload: 0",
+            "die: {now: true}",
+            "die: []",
+        ] {
+            assert!(action(yaml).is_err(), "{}", yaml);
+        }
+    }
+
+    fn context() -> Context {
+        let mut context = Context::default();
+        context.set_var("key", "a");
+        context.set_var("window_title", "it's $(here)");
+        context
+    }
+
+    #[test]
+    fn substitute() {
+        let context = context();
+        assert_eq!(
+            context.substitute("{key}: {window_title}"),
+            "a: it's $(here)"
+        );
+        assert_eq!(
+            context.substitute("${key} {unknown} {key"),
+            "${key} {unknown} {key"
+        );
+        assert_eq!(context.env["KEYTREE_WINDOW_TITLE"], "it's $(here)");
+    }
+
+    #[test]
+    fn scripts_not_substituted() {
+        let execute = |yaml| match action(yaml).unwrap() {
+            Action::Execute(execute) => execute.resolve(&context()),
+            action => panic!("{:?}", action),
+        };
+
+        let script = execute("execute: echo \"{window_title}\"");
+        assert_eq!(script.script(), Some("echo \"{window_title}\""));
+        let command =
+            match execute("execute: {argv: [echo, \"{window_title}\"], env: {K: \"{key}\"}}") {
+                Execute::Command(command) => command,
+                execute => panic!("{:?}", execute),
+            };
+        assert_eq!(command.argv, ["echo", "it's $(here)"]);
+        assert_eq!(command.env["K"], "a");
+    }
+
+    #[test]
+    fn described_as_written() {
+        let execute = |yaml| match action(yaml).unwrap() {
+            Action::Execute(execute) => execute,
+            action => panic!("{:?}", action),
+        };
+
+        let script = execute("execute: {script: echo $KEYTREE_KEY, shell: bash, env: {A: b}}");
+        assert_eq!(script.describe(), "echo $KEYTREE_KEY");
+        let argv = execute("execute: {argv: [notify-send, \"{window_title}\"], cwd: /tmp}");
+        assert_eq!(argv.describe(), "notify-send '{window_title}'");
+        assert_eq!(
+            execute("execute: echo a").resolve(&context()).describe(),
+            "echo a"
+        );
+    }
+
+    #[test]
+    fn placeholders_in_scripts() {
+        assert_eq!(
+            placeholders("echo \"{window_title}\" {key}"),
+            ["key", "window_title"]
+        );
+        assert!(placeholders("echo ${key} {unknown} $KEYTREE_KEY").is_empty());
+    }
+
+    #[test]
+    fn quote() {
+        assert_eq!(shell_quote("a-b/c.d"), "a-b/c.d");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
@@ -0,0 +1,347 @@
+//! The YAML parser drops comments, so they are carried over to the formatted
+//! text by the key path of the line they precede or end. Lines are read in
+//! the block style, which is what `serde_yaml` writes and what configuration
+//! files mostly use; comments inside flow collections are lost.
+
+use super::normalize_key;
+
+/// What a line of a YAML file is, for the comments.
+enum Line<'a> {
+    Blank,
+    Comment(&'a str),
+    /// The start of a mapping entry or a sequence item, by key path, with the
+    /// comment ending the line
+    Entry(Vec<String>, Option<&'a str>),
+    Other,
+}
+
+struct Level {
+    indent: usize,
+    name: String,
+    /// Position of a sequence item among its siblings
+    item: Option<usize>,
+}
+
+/// Follows the nesting of the lines of a file, by their indentation.
+#[derive(Default)]
+struct Tracker {
+    levels: Vec<Level>,
+    /// Indentation of the entry whose value is a block scalar, as long as the
+    /// lines belong to it
+    block: Option<usize>,
+}
+
+/// The start of the comment ending a line, if any: a `#` after a space,
+/// outside of quoted scalars.
+fn comment_start(content: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+
+    for (idx, c) in content.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => {
+                if c == '#' && prev == ' ' {
+                    return Some(idx);
+                }
+                // Quotes only start scalars, not inside plain ones
+                let at_start = prev == ' ' || prev == '[' || prev == '{' || prev == ',';
+                if (c == '"' || c == '\'') && at_start {
+                    quote = Some(c);
+                }
+            }
+        }
+        prev = c;
+    }
+
+    None
+}
+
+/// The key of a mapping entry and the rest of the line after it.
+fn split_key(content: &str) -> Option<(String, &str)> {
+    if content.starts_with('"') || content.starts_with('\'') {
+        let q = content.chars().next()?;
+        let mut escaped = false;
+        let end = content.char_indices().skip(1).find(|&(_, c)| {
+            let end = c == q && !escaped;
+            escaped = q == '"' && c == '\\' && !escaped;
+            end
+        })?;
+        let (key, rest) = content.split_at(end.0 + 1);
+        let rest = rest.strip_prefix(':')?;
+        if !rest.is_empty() && !rest.starts_with(' ') {
+            return None;
+        }
+        return Some((serde_yaml::from_str(key).ok()?, rest));
+    }
+
+    if content.starts_with('{') || content.starts_with('[') {
+        return None;
+    }
+    let end = match content.find(": ") {
+        Some(end) => end,
+        None if content.ends_with(':') => content.len() - 1,
+        None => return None,
+    };
+
+    Some((content[..end].trim_end().to_owned(), &content[end + 1..]))
+}
+
+impl Tracker {
+    fn path(&self) -> Vec<String> {
+        self.levels.iter().map(|level| level.name.clone()).collect()
+    }
+
+    fn line<'a>(&mut self, line: &'a str) -> Line<'a> {
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+
+        if let Some(block) = self.block {
+            if content.is_empty() || indent > block {
+                return Line::Other;
+            }
+            self.block = None;
+        }
+        if content.is_empty() {
+            return Line::Blank;
+        }
+        if content.starts_with('#') {
+            return Line::Comment(content.trim_end());
+        }
+
+        let (content, comment) = match comment_start(content) {
+            Some(start) => (&content[..start], Some(content[start..].trim_end())),
+            None => (content, None),
+        };
+        let mut rest = content.trim_end();
+        let mut column = indent;
+        let mut entry = false;
+
+        while rest == "-" || rest.starts_with("- ") {
+            let mut index = 0;
+            while let Some(top) = self.levels.last() {
+                if top.indent < column || (top.indent == column && top.item.is_none()) {
+                    break;
+                }
+                if let (true, Some(previous)) = (top.indent == column, top.item) {
+                    index = previous + 1;
+                }
+                self.levels.pop();
+            }
+            self.levels.push(Level {
+                indent: column,
+                name: format!("-{}", index),
+                item: Some(index),
+            });
+
+            let after = rest[1..].trim_start();
+            column += rest.len() - after.len();
+            rest = after;
+            entry = true;
+        }
+
+        if let Some((key, value)) = split_key(rest) {
+            while self.levels.last().is_some_and(|top| top.indent >= column) {
+                self.levels.pop();
+            }
+            self.levels.push(Level {
+                indent: column,
+                name: key,
+                item: None,
+            });
+
+            let value = value.trim();
+            if value.starts_with('|') || value.starts_with('>') {
+                self.block = Some(column);
+            }
+            entry = true;
+        }
+
+        if entry {
+            Line::Entry(self.path(), comment)
+        } else {
+            Line::Other
+        }
+    }
+}
+
+/// A key path with the keys of `map` sections in canonical form.
+fn normalize_path(mut path: Vec<String>) -> Vec<String> {
+    for idx in 1..path.len() {
+        if path[idx - 1] == "map" {
+            path[idx] = normalize_key(&path[idx]);
+        }
+    }
+    path
+}
+
+/// Whether a path read from the original file designates an entry of the
+/// formatted one, whose keys may have been collapsed or expanded.
+fn matches(original: &[String], formatted: &[String]) -> bool {
+    original.len() == formatted.len()
+        && (0..original.len()).all(|idx| {
+            original[idx] == formatted[idx]
+                || (idx > 0
+                    && original[idx - 1] == "map"
+                    && original[idx]
+                        .split(',')
+                        .any(|alias| formatted[idx].split(',').any(|other| other == alias)))
+        })
+}
+
+/// Put the comments of the original text back into the formatted one.
+pub(super) fn restore(original: &str, formatted: &str) -> String {
+    let mut tracker = Tracker::default();
+    let mut header = vec![];
+    let mut pending = vec![];
+    let mut before: Vec<(Vec<String>, Vec<&str>)> = vec![];
+    let mut after: Vec<(Vec<String>, &str)> = vec![];
+    let mut first_entry = true;
+
+    for line in original.lines() {
+        match tracker.line(line) {
+            // Comments at the top, apart from the first entry, stay there
+            Line::Blank if first_entry => header.append(&mut pending),
+            Line::Blank | Line::Other => {}
+            Line::Comment(comment) => pending.push(comment),
+            Line::Entry(path, comment) => {
+                first_entry = false;
+                let path = normalize_path(path);
+                if !pending.is_empty() {
+                    before.push((path.clone(), std::mem::take(&mut pending)));
+                }
+                if let Some(comment) = comment {
+                    after.push((path, comment));
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for comment in header.iter() {
+        out.push_str(comment);
+        out.push('\n');
+    }
+    if !header.is_empty() {
+        out.push('\n');
+    }
+
+    let mut tracker = Tracker::default();
+    for line in formatted.lines() {
+        if let Line::Entry(path, _) = tracker.line(line) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            while let Some(idx) = before.iter().position(|(p, _)| matches(p, &path)) {
+                for comment in before.remove(idx).1 {
+                    out.push_str(indent);
+                    out.push_str(comment);
+                    out.push('\n');
+                }
+            }
+
+            out.push_str(line);
+            while let Some(idx) = after.iter().position(|(p, _)| matches(p, &path)) {
+                out.push_str("  ");
+                out.push_str(after.remove(idx).1);
+            }
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+
+    // Comments of entries that are gone, and those ending the file
+    let left = before.into_iter().flat_map(|(_, comments)| comments);
+    let left = left.chain(after.into_iter().map(|(_, comment)| comment));
+    for comment in left.chain(pending) {
+        out.push_str(comment);
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment_starts() {
+        assert_eq!(comment_start("a: b # c"), Some(5));
+        assert_eq!(comment_start("a: b#c"), None);
+        assert_eq!(comment_start("a: \"b # c\" # d"), Some(11));
+        assert_eq!(comment_start("a: 'it''s' # d"), Some(11));
+        assert_eq!(comment_start("a: don't # d"), Some(9));
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(split_key("title: x"), Some(("title".to_owned(), " x")));
+        assert_eq!(split_key("map:"), Some(("map".to_owned(), "")));
+        assert_eq!(split_key("\"a: b\": x"), Some(("a: b".to_owned(), " x")));
+        assert_eq!(
+            split_key("execute: a:b"),
+            Some(("execute".to_owned(), " a:b"))
+        );
+        assert_eq!(split_key("plain"), None);
+        assert_eq!(split_key("{a: b}"), None);
+    }
+
+    #[test]
+    fn paths() {
+        let mut tracker = Tracker::default();
+        let text = "map:\n  a:\n    list:\n      - execute: x # run\n      - reload: ~\n    title: |\n      b: c\n  b: {}\n";
+        let entries: Vec<(String, Option<&str>)> = text
+            .lines()
+            .filter_map(|line| match tracker.line(line) {
+                Line::Entry(path, comment) => Some((path.join("."), comment)),
+                _ => None,
+            })
+            .collect();
+
+        let expected = [
+            ("map", None),
+            ("map.a", None),
+            ("map.a.list", None),
+            ("map.a.list.-0.execute", Some("# run")),
+            ("map.a.list.-1.reload", None),
+            ("map.a.title", None),
+            ("map.b", None),
+        ];
+        let expected: Vec<(String, Option<&str>)> = expected
+            .iter()
+            .map(|(path, comment)| (path.to_string(), *comment))
+            .collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn restore_comments() {
+        let original = "# My keys\n\nmap:\n  # Letters\n  b:\n    execute: b # second\n  Control-a:\n    execute: a\n# The end\n";
+        let formatted = "map:\n  C-a:\n    execute: a\n  b:\n    execute: b\n";
+
+        assert_eq!(
+            restore(original, formatted),
+            "# My keys\n\nmap:\n  C-a:\n    execute: a\n  # Letters\n  b:\n    execute: b  # second\n# The end\n"
+        );
+    }
+
+    #[test]
+    fn restore_collapsed() {
+        let original = "map:\n  a:\n    execute: x # on a\n  b:\n    # on b\n    execute: x\n";
+        let formatted = "map:\n  a,b:\n    execute: x\n";
+
+        assert_eq!(
+            restore(original, formatted),
+            "map:\n  a,b:\n    # on b\n    execute: x  # on a\n"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::action::FIELDS;
+use crate::combination;
+use crate::config::{self, Config, Format};
+use crate::error::Error;
+use crate::theme::Theme;
+
+mod comments;
+
+/// Top-level fields of a configuration file, in the order in which they are
+/// written.
+const CONFIG_FIELDS: &[&str] = &["markup", "settings", "theme", "include", "map"];
+
+/// How bindings shared by several keys are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Aliases {
+    /// Under the keys they were written with
+    Keep,
+    /// Under a single comma-separated key, `a,b`
+    Collapse,
+    /// Under each of the keys
+    Expand,
+}
+
+impl FromStr for Aliases {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "keep" => Aliases::Keep,
+            "collapse" => Aliases::Collapse,
+            "expand" => Aliases::Expand,
+            _ => return Err(Error::InvalidAliases(s.to_owned())),
+        })
+    }
+}
+
+/// The canonical spelling of a key: its combinations normalized, sorted, and
+/// without duplicates. Keys that are not valid combinations are kept as they
+/// are.
+pub(crate) fn normalize_key(key: &str) -> String {
+    let mut aliases: Vec<_> = key
+        .split(',')
+        .map(|alias| combination::normalize(alias).unwrap_or_else(|| alias.to_owned()))
+        .collect();
+    aliases.sort();
+    aliases.dedup();
+    aliases.join(",")
+}
+
+/// The fields of a mapping, with those of `order` first and in that order,
+/// followed by the others as they were.
+fn reorder(mut mapping: Mapping, order: &[&str]) -> Mapping {
+    let mut sorted = Mapping::new();
+
+    for field in order {
+        let field = Value::from(*field);
+        if let Some(value) = mapping.remove(&field) {
+            sorted.insert(field, value);
+        }
+    }
+    for (key, value) in mapping {
+        sorted.insert(key, value);
+    }
+
+    sorted
+}
+
+fn key_string(key: &Value) -> Option<String> {
+    match key {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn canonical_node(node: Value, aliases: Aliases, path: &mut Vec<String>) -> Result<Value, Error> {
+    let mut node = match node {
+        Value::Mapping(node) => node,
+        node => return Ok(node),
+    };
+
+    let map = Value::from("map");
+    if let Some(children) = node.remove(&map) {
+        node.insert(map, canonical_map(children, aliases, path)?);
+    }
+
+    Ok(Value::Mapping(reorder(node, FIELDS)))
+}
+
+/// Whether a binding can be split between its keys, or share a key with
+/// others: deletions apply to the key as written, and `map` nodes are merged
+/// with the other files by key.
+fn is_leaf(node: &Value) -> bool {
+    match node {
+        Value::Mapping(node) => !node.contains_key(&Value::from("map")),
+        _ => false,
+    }
+}
+
+fn canonical_map(map: Value, aliases: Aliases, path: &mut Vec<String>) -> Result<Value, Error> {
+    let map = match map {
+        Value::Mapping(map) => map,
+        map => return Ok(map),
+    };
+
+    let mut bindings: Vec<(String, Value)> = vec![];
+    let mut others = Mapping::new();
+
+    for (key, node) in map {
+        let key = match key_string(&key) {
+            Some(key) => normalize_key(&key),
+            None => {
+                others.insert(key, node);
+                continue;
+            }
+        };
+
+        path.push(key.clone());
+        let node = canonical_node(node, aliases, path)?;
+        path.pop();
+
+        if !is_leaf(&node) {
+            bindings.push((key, node));
+            continue;
+        }
+
+        match aliases {
+            Aliases::Keep => bindings.push((key, node)),
+            Aliases::Expand => {
+                for alias in key.split(',') {
+                    bindings.push((alias.to_owned(), node.clone()));
+                }
+            }
+            Aliases::Collapse => {
+                let same = bindings
+                    .iter_mut()
+                    .find(|(_, other)| is_leaf(other) && *other == node);
+                match same {
+                    Some((other_key, _)) => {
+                        *other_key = normalize_key(&format!("{},{}", other_key, key))
+                    }
+                    None => bindings.push((key, node)),
+                }
+            }
+        }
+    }
+
+    bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut sorted = Mapping::new();
+    for (key, node) in bindings {
+        if sorted.contains_key(&Value::from(key.as_str())) {
+            path.push(key);
+            return Err(Error::ConflictingBindings(format!(
+                "{}: written more than once",
+                path.join(" ")
+            )));
+        }
+        sorted.insert(Value::from(key), node);
+    }
+    for (key, node) in others {
+        sorted.insert(key, node);
+    }
+
+    Ok(Value::Mapping(sorted))
+}
+
+/// A configuration in canonical form: combinations spelled the same way,
+/// bindings sorted by key, fields in a fixed order, and aliases kept, collapsed
+/// or expanded. Unknown fields are kept.
+fn canonical(config: Value, aliases: Aliases) -> Result<Value, Error> {
+    let mut config = match config {
+        Value::Mapping(config) => config,
+        config => return Ok(config),
+    };
+
+    let map = Value::from("map");
+    if let Some(tree) = config.remove(&map) {
+        config.insert(map, canonical_map(tree, aliases, &mut vec![])?);
+    }
+
+    Ok(Value::Mapping(reorder(config, CONFIG_FIELDS)))
+}
+
+/// YAML text, without the document start.
+fn yaml(value: &Value) -> Result<String, Error> {
+    let out = serde_yaml::to_string(value)?;
+    Ok(out.strip_prefix("---\n").unwrap_or(&out).to_owned())
+}
+
+/// The YAML text of a configuration built by keytree, such as an imported one,
+/// in canonical form and without the sections left to their defaults.
+pub(crate) fn to_yaml(config: &Config) -> Result<String, Error> {
+    let mut config = serde_yaml::to_value(config)?;
+    let theme = Value::from("theme");
+    if let Value::Mapping(fields) = &mut config {
+        if fields.get(&theme) == Some(&serde_yaml::to_value(Theme::default())?) {
+            fields.remove(&theme);
+        }
+    }
+
+    yaml(&canonical(config, Aliases::Keep)?)
+}
+
+/// The text of a configuration file in canonical form. The comments of YAML
+/// files are kept, beside the keys they were written with.
+pub(crate) fn format(path: &Path, text: &str, aliases: Aliases) -> Result<String, Error> {
+    let format = Format::from_path(path)?;
+    if format == Format::Yaml && config::is_blank(text) {
+        return Ok(text.to_owned());
+    }
+
+    let config = canonical(format.parse(path, text)?, aliases)?;
+
+    Ok(match format {
+        Format::Yaml => comments::restore(text, &yaml(&config)?),
+        Format::Json => serde_json::to_string_pretty(&config)? + "\n",
+        Format::Toml => toml::to_string(&toml::Value::try_from(&config)?)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_map(yaml: &str, aliases: Aliases) -> String {
+        let config = canonical(serde_yaml::from_str(yaml).unwrap(), aliases).unwrap();
+        serde_yaml::to_string(&config["map"]).unwrap()
+    }
+
+    const SHARED: &str =
+        "map:\n  b: {execute: x}\n  Control-a: {execute: x}\n  c,a: {execute: y}\n";
+
+    #[test]
+    fn keys_kept_by_default() {
+        assert_eq!(
+            format_map(SHARED, Aliases::Keep),
+            "---\nC-a:\n  execute: x\n\"a,c\":\n  execute: y\nb:\n  execute: x\n"
+        );
+    }
+
+    #[test]
+    fn aliases_collapsed() {
+        assert_eq!(
+            format_map(SHARED, Aliases::Collapse),
+            "---\n\"C-a,b\":\n  execute: x\n\"a,c\":\n  execute: y\n"
+        );
+    }
+
+    #[test]
+    fn aliases_expanded() {
+        assert!(format_map(SHARED, Aliases::Expand)
+            .starts_with("---\nC-a:\n  execute: x\na:\n  execute: y\nb:"));
+        let twice = "map:\n  a: {execute: x}\n  a,b: {execute: y}\n";
+        assert!(canonical(serde_yaml::from_str(twice).unwrap(), Aliases::Expand).is_err());
+    }
+
+    #[test]
+    fn built_config() {
+        let config: Config =
+            serde_yaml::from_str("map: {b: {execute: x}, Control-a: {execute: y}}").unwrap();
+        assert_eq!(
+            to_yaml(&config).unwrap(),
+            "map:\n  C-a:\n    execute: y\n  b:\n    execute: x\n"
+        );
+
+        let config: Config = serde_yaml::from_str("theme: {padding: 4}").unwrap();
+        assert!(to_yaml(&config).unwrap().starts_with("theme:\n"));
+    }
+
+    #[test]
+    fn fields_ordered() {
+        let yaml = "map:\n  a: {execute: x, title: t, unknown: 1}\ntheme: {}\nmarkup: true\n";
+        let config = canonical(serde_yaml::from_str(yaml).unwrap(), Aliases::Keep).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&config).unwrap(),
+            "---\nmarkup: true\ntheme: {}\nmap:\n  a:\n    title: t\n    execute: x\n    unknown: 1\n"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Commands started by the bindings. They run detached from keytree, in a
+//! session of their own and without its file descriptors, so that stopping or
+//! restarting keytree neither kills them nor leaves them as zombies.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsFd, BorrowedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::sys::memfd::{memfd_create, MFdFlags};
+use nix::sys::signal::{killpg, SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::unistd::Pid;
+
+/// Lines of output shown with a failure
+const TAIL_LINES: usize = 5;
+
+/// Time left to a stopped command to exit before it is killed
+const STOP_GRACE: Duration = Duration::from_secs(3);
+
+/// Where the output of a command goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Output {
+    Discard,
+    /// To a new log file
+    Log,
+    /// To keytree, once the command has exited
+    Keep,
+}
+
+struct Running {
+    child: Child,
+    command: String,
+    log: Option<PathBuf>,
+    kept: Option<File>,
+    /// When the command is killed, once it was asked to stop
+    kill_at: Option<Instant>,
+}
+
+/// A command that has finished.
+pub(crate) struct Exited {
+    pub pid: u32,
+    pub command: String,
+    pub status: ExitStatus,
+    /// File holding the standard output and error, when they were captured
+    pub log: Option<PathBuf>,
+    /// The output, when it was kept
+    pub output: Option<String>,
+}
+
+impl Exited {
+    /// The report of a failed command whose output was captured: its status
+    /// and the last lines it wrote, to its standard output or error.
+    pub(crate) fn failure(&self) -> Option<String> {
+        let log = match (&self.log, self.status.success()) {
+            (Some(log), false) => log,
+            _ => return None,
+        };
+
+        let mut text = format!("`{}` failed, {}", self.command, self.status);
+        let output = std::fs::read_to_string(log).unwrap_or_default();
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+        if !lines.is_empty() {
+            text.push_str(", last output:");
+        }
+        for line in &lines[lines.len().saturating_sub(TAIL_LINES)..] {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push_str(&format!("\n(see {})", log.display()));
+
+        Some(text)
+    }
+}
+
+pub(crate) struct Children {
+    running: Vec<Running>,
+    /// Readable when a child has exited
+    exited: SignalFd,
+    /// Captured runs so far, numbering the log files
+    runs: usize,
+}
+
+impl Children {
+    /// Start receiving SIGCHLD through a descriptor. This must happen before
+    /// any thread is started, for the signal to be blocked in all of them.
+    pub(crate) fn new() -> io::Result<Self> {
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGCHLD);
+        mask.thread_block()?;
+
+        let flags = SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC;
+        Ok(Self {
+            running: vec![],
+            exited: SignalFd::with_flags(&mask, flags)?,
+            runs: 0,
+        })
+    }
+
+    /// The descriptor to poll for exited children.
+    pub(crate) fn fd(&self) -> BorrowedFd<'_> {
+        self.exited.as_fd()
+    }
+
+    /// A new log file for a captured run, starting with the command line.
+    fn log_file(&mut self, command: &str) -> io::Result<(PathBuf, File)> {
+        let dir = logs_dir()?;
+        std::fs::create_dir_all(&dir)?;
+
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.runs += 1;
+        let path = dir.join(format!("{}-{}-{}.log", time, std::process::id(), self.runs));
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "$ {}", command)?;
+        Ok((path, file))
+    }
+
+    /// Start a command, described by `command` in logs and reports, and
+    /// return its process id.
+    pub(crate) fn spawn(
+        &mut self,
+        mut process: Command,
+        command: String,
+        output: Output,
+    ) -> io::Result<u32> {
+        close_on_exec()?;
+        process.stdin(Stdio::null());
+
+        let mut log = None;
+        let mut kept = None;
+        match output {
+            Output::Discard => {
+                process.stdout(Stdio::null()).stderr(Stdio::null());
+            }
+            Output::Log => {
+                let (path, file) = self.log_file(&command)?;
+                process.stdout(file.try_clone()?).stderr(file);
+                log = Some(path);
+            }
+            Output::Keep => {
+                let file = File::from(memfd_create("keytree-output", MFdFlags::MFD_CLOEXEC)?);
+                process.stdout(file.try_clone()?).stderr(file.try_clone()?);
+                kept = Some(file);
+            }
+        }
+
+        // Out of keytree's process group and controlling terminal, so that
+        // signals sent to keytree don't reach the command
+        unsafe {
+            process.pre_exec(|| {
+                nix::unistd::setsid()?;
+                Ok(())
+            });
+        }
+
+        let child = process.spawn()?;
+        let pid = child.id();
+        log::debug!("Started process {}: {}", pid, command);
+        self.running.push(Running {
+            child,
+            command,
+            log,
+            kept,
+            kill_at: None,
+        });
+        Ok(pid)
+    }
+
+    /// Terminate a command, with the processes it started. Those still
+    /// running after a grace period are killed by `kill_stopped`.
+    pub(crate) fn stop(&mut self, pid: u32) {
+        signal(pid, Signal::SIGTERM);
+        if let Some(run) = self.running.iter_mut().find(|run| run.child.id() == pid) {
+            run.kill_at = Some(Instant::now() + STOP_GRACE);
+        }
+    }
+
+    /// Kill the commands which were stopped but have not exited in time.
+    pub(crate) fn kill_stopped(&mut self) {
+        let now = Instant::now();
+        for run in self.running.iter_mut() {
+            if run.kill_at.is_some_and(|kill_at| kill_at <= now) {
+                log::warn!("Killing process {}, which did not stop", run.child.id());
+                signal(run.child.id(), Signal::SIGKILL);
+                run.kill_at = None;
+            }
+        }
+    }
+
+    /// Collect the children that have exited, once SIGCHLD was received.
+    pub(crate) fn reap(&mut self) -> Vec<Exited> {
+        let mut signaled = false;
+        while let Ok(Some(_)) = self.exited.read_signal() {
+            signaled = true;
+        }
+        if !signaled {
+            return vec![];
+        }
+
+        let mut exited = vec![];
+        let mut running = vec![];
+        for mut run in self.running.drain(..) {
+            match run.child.try_wait() {
+                Ok(Some(status)) => {
+                    log::debug!("Process {} exited: {}", run.child.id(), status);
+                    exited.push(Exited {
+                        pid: run.child.id(),
+                        command: run.command,
+                        status,
+                        log: run.log,
+                        output: run.kept.map(read_output),
+                    });
+                }
+                Ok(None) => running.push(run),
+                Err(_) => {}
+            }
+        }
+        self.running = running;
+
+        exited
+    }
+}
+
+/// Send a signal to a command and the processes it started: each command
+/// leads a process group of its own.
+fn signal(pid: u32, signal: Signal) {
+    if let Err(err) = killpg(Pid::from_raw(pid as i32), signal) {
+        log::warn!("Could not send {} to process {}: {}", signal, pid, err);
+    }
+}
+
+/// The output a command has written to a file, from its start.
+fn read_output(mut file: File) -> String {
+    let mut output = vec![];
+    if let Err(err) = file.seek(SeekFrom::Start(0)).and_then(|_| file.read_to_end(&mut output)) {
+        log::warn!("Could not read the output of a command: {}", err);
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Where the output of commands is written: `$XDG_STATE_HOME/keytree/logs`.
+fn logs_dir() -> io::Result<PathBuf> {
+    let state = match std::env::var_os("XDG_STATE_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => dirs::home_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?
+            .join(".local/state"),
+    };
+
+    Ok(state.join("keytree").join("logs"))
+}
+
+/// Keep the descriptors opened outside of the standard library, such as the
+/// X connection, from the children.
+fn close_on_exec() -> io::Result<()> {
+    for entry in std::fs::read_dir("/proc/self/fd")? {
+        let name = entry?.file_name();
+        let fd: RawFd = match name.to_str().and_then(|name| name.parse().ok()) {
+            Some(fd) if fd > 2 => fd,
+            _ => continue,
+        };
+
+        // Every descriptor listed is still open, keytree being single-threaded
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        let _ = fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+    }
+
+    Ok(())
+}
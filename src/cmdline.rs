@@ -1,4 +1,11 @@
+use crate::canonical::Aliases;
+use crate::config;
 use crate::error::Error;
+use crate::export::Format;
+use crate::import::Source;
+use crate::overrides::Override;
+use crate::placement::{Anchor, Placement};
+use crate::settings::LogLevel;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -16,16 +23,135 @@ pub(crate) struct Opt {
     #[structopt(long, short = "r")]
     pub root_key: Option<String>,
 
-    /// Font to use (Pango font string, for example "normal 100" for big text)
-    #[structopt(long = "font", short = "n", default_value = "normal 25")]
-    pub font: String,
+    /// Font to use (Pango font string, for example "normal 100" for big text). Default:
+    /// "normal 25"
+    #[structopt(long = "font", short = "n")]
+    pub font: Option<String>,
 
-    /// Initial screen position
-    #[structopt(long = "position", short = "p", default_value = "%50,%50")]
-    pub position: String,
+    /// Initial screen position. Default: "%50,%50"
+    #[structopt(long = "position", short = "p")]
+    pub position: Option<String>,
+
+    /// Monitor on which to show windows: largest (the default), primary, pointer, focused,
+    /// or fixed (the whole X screen)
+    #[structopt(long = "placement")]
+    pub placement: Option<Placement>,
+
+    /// Attach windows to a point of the monitor instead of using --position: top-left, top,
+    /// top-right, left, center, right, bottom-left, bottom, or bottom-right
+    #[structopt(long = "anchor")]
+    pub anchor: Option<Anchor>,
+
+    /// Distance in pixels kept between anchored windows and the monitor edges. Default: 0
+    #[structopt(long = "margin")]
+    pub margin: Option<u16>,
+
+    /// Most verbose messages to log: off, error, warn (the default), info, debug, or trace
+    #[structopt(long = "log-level")]
+    pub log_level: Option<LogLevel>,
+
+    /// Program running execute actions with -c. Default: sh
+    #[structopt(long = "shell")]
+    pub shell: Option<String>,
+
+    /// Change a value of the configuration, after the files and the KEYTREE_SET,
+    /// KEYTREE_SET_* and KEYTREE_CONFIG__* environment variables: settings.font="normal 20", or map[Menu s].execute=script to
+    /// change a node of the tree. A null value (~) removes the target. Can be repeated.
+    #[structopt(long = "set", number_of_values = 1)]
+    pub set: Vec<Override>,
+
+    /// Read the monitor layout from this file instead of querying RandR. Each line holds a
+    /// JSON object such as {"name": "HDMI-1", "x": 0, "y": 0, "width": 1920, "height": 1080}
+    #[structopt(long = "monitor-cache")]
+    pub monitor_cache: Option<PathBuf>,
 
     #[structopt(long = "show-example-config")]
     pub example_config: bool,
+
+    /// Format of --show-example-config: yaml, json, or toml
+    #[structopt(long = "format", default_value = "yaml")]
+    pub format: config::Format,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub(crate) enum Command {
+    /// Draw the OSD of a node to a PNG or SVG file, without connecting to X
+    Render {
+        /// Space-separated key combinations leading to the node, for example "Menu s". The
+        /// default is the root of the tree.
+        #[structopt(long, default_value = "")]
+        path: String,
+
+        /// Output file, with the format chosen by the extension: .png or .svg
+        #[structopt(long)]
+        out: PathBuf,
+
+        /// Width of the monitor for which the OSD is laid out
+        #[structopt(long, default_value = "1920")]
+        width: u16,
+
+        /// Height of the monitor for which the OSD is laid out
+        #[structopt(long, default_value = "1080")]
+        height: u16,
+    },
+
+    /// Load the configuration without connecting to X, and show the files it was read from
+    /// and the overrides applied to it
+    Check,
+
+    /// Rewrite a configuration file in canonical form: combinations spelled the same way,
+    /// bindings sorted by key and fields in a fixed order. YAML comments are kept.
+    Fmt {
+        /// Only check that the file is in canonical form, failing if it is not
+        #[structopt(long)]
+        check: bool,
+
+        /// How to write bindings of several keys: keep (as written), collapse (identical
+        /// bindings under one key such as `a,b`) or expand (under each key)
+        #[structopt(long, default_value = "keep")]
+        aliases: Aliases,
+
+        /// File to format, by default the user's configuration file
+        path: Option<PathBuf>,
+    },
+
+    /// Print a JSON Schema of the configuration file, for editors to complete and validate it
+    Schema,
+
+    /// Print every binding of the tree with its title and action, as a reference to share
+    /// or print
+    Export {
+        /// Output format: markdown, html, pdf, svg, dot (Graphviz), or sxhkd. Bindings that
+        /// the format can't express are listed on standard error.
+        #[structopt(long, default_value = "markdown")]
+        format: Format,
+
+        /// Output file, standard output by default
+        #[structopt(long)]
+        out: Option<PathBuf>,
+
+        /// Font of the pdf and svg cheat sheets (Pango font string)
+        #[structopt(long = "sheet-font", default_value = "sans 9")]
+        sheet_font: String,
+    },
+
+    /// Convert the bindings of another hotkey daemon to a keytree configuration. Bindings
+    /// which can't be translated are listed on standard error.
+    Import {
+        /// Format of the input: sxhkd or xbindkeys
+        #[structopt(long)]
+        from: Source,
+
+        /// Configuration file to convert, for example ~/.config/sxhkd/sxhkdrc
+        input: PathBuf,
+
+        /// Output file, standard output by default
+        #[structopt(long)]
+        out: Option<PathBuf>,
+    },
 }
 
 pub(crate) fn parse_position(v: &str, measure: u16, screen_measure: u16) -> Result<i16, Error> {
@@ -1,8 +1,26 @@
+use std::collections::BTreeSet;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+
 use crate::keysym::{self, KeySym};
 use crate::Error;
 
 pub type KeyCombination = String;
 
+type SetModifier = fn(&mut Modifiers);
+
+/// Prefixes of the modifiers in combinations such as `C-x`, with the flag
+/// each one sets.
+const MODIFIERS: &[(&[&str], SetModifier)] = &[
+    (&["C", "Ctrl", "Control"], |m| m.control = true),
+    (&["S", "Sup", "Super"], |m| m.superr = true),
+    (&["M", "Meta"], |m| m.meta = true),
+    (&["A", "Alt"], |m| m.alt = true),
+    (&["H", "Hyp", "Hyper"], |m| m.hyper = true),
+];
+
 #[derive(Default)]
 pub struct Modifiers {
     pub control: bool,
@@ -12,6 +30,12 @@ pub struct Modifiers {
     pub superr: bool,
 }
 
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        !(self.control || self.alt || self.meta || self.hyper || self.superr)
+    }
+}
+
 pub struct Combination {
     pub key: KeySym,
     pub modifiers: Modifiers,
@@ -45,13 +69,8 @@ impl Combination {
         let mut mods = Modifiers::default();
 
         for i in 0..v.len() - 1 {
-            match v[i] {
-                "C" | "Ctrl" | "Control" => mods.control = true,
-                "S" | "Sup" | "Super" => mods.superr = true,
-                "M" | "Meta" => mods.meta = true,
-                "A" | "Alt" => mods.alt = true,
-                "H" | "Hyp" | "Hyper" => mods.hyper = true,
-                _ => {}
+            if let Some((_, set)) = MODIFIERS.iter().find(|(names, _)| names.contains(&v[i])) {
+                set(&mut mods);
             }
         }
 
@@ -65,3 +84,59 @@ impl Combination {
         }
     }
 }
+
+/// The canonical spelling of a combination, such as `C-x` for `Control-x`, or
+/// `None` if it is not a valid one.
+pub(crate) fn normalize(s: &str) -> Option<String> {
+    let v: Vec<_> = s.split('-').collect();
+    let known = |prefix: &&str| MODIFIERS.iter().any(|(names, _)| names.contains(prefix));
+    if !v[..v.len() - 1].iter().all(known) {
+        return None;
+    }
+
+    Combination::parse(s).ok().map(|c| c.to_string())
+}
+
+/// The combinations bound by a key of a `map`, in canonical spelling, so that
+/// `Control-x,a` and `a,C-x` compare equal. Invalid ones are kept as written.
+pub(crate) fn aliases(key: &str) -> BTreeSet<String> {
+    key.split(',')
+        .map(|alias| normalize(alias).unwrap_or_else(|| alias.to_owned()))
+        .collect()
+}
+
+/// Schema of the keys of a `map`: a combination, or several separated by
+/// commas, made of modifier prefixes and a keysym name.
+pub(crate) struct KeySchema;
+
+impl JsonSchema for KeySchema {
+    fn schema_name() -> String {
+        "KeyCombination".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let modifiers: Vec<&str> = MODIFIERS
+            .iter()
+            .flat_map(|(names, _)| names.iter().copied())
+            .collect();
+        let keys = keysym::names();
+        let keys = keys.iter().map(|name| regex::escape(name)).collect::<Vec<_>>();
+        let combination = format!("(?:(?:{})-)*(?:{})", modifiers.join("|"), keys.join("|"));
+
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "A key combination such as `C-x`, or several separated by commas".to_owned(),
+                ),
+                ..Default::default()
+            })),
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(format!("^{0}(?:,{0})*$", combination)),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
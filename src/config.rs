@@ -1,16 +1,596 @@
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::action::{Action, ActionDesc, Op};
-use crate::combination::KeyCombination;
+use crate::action::{Action, ActionDesc, Execute, NoArgs, Op};
+use crate::combination::{self, KeyCombination};
+use crate::error::Error;
+use crate::overrides::Override;
+use crate::settings::Settings;
+use crate::theme::Theme;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 pub struct Config {
+    /// Parse all titles as Pango markup, unless overridden by a node
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub markup: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Settings::is_default")]
+    pub settings: Settings,
+    #[serde(default)]
+    pub theme: Theme,
+    /// Other files whose maps are merged into this one: paths or glob
+    /// patterns, relative to the directory of this file
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::action::deserialize_map")]
+    #[schemars(schema_with = "crate::action::map_schema")]
     pub map: HashMap<KeyCombination, ActionDesc>,
+    /// Every file the configuration was read from
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+    /// Changes made after reading the files
+    #[serde(skip)]
+    pub overrides: Vec<Override>,
+}
+
+/// Formats in which a configuration can be written, chosen by file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Yaml,
+    Json,
+    Toml,
+}
+
+/// Recognized extensions, in the order in which the default configuration
+/// files are searched.
+const EXTENSIONS: &[(&str, Format)] = &[
+    ("yaml", Format::Yaml),
+    ("yml", Format::Yaml),
+    ("json", Format::Json),
+    ("toml", Format::Toml),
+];
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        EXTENSIONS
+            .iter()
+            .find(|(ext, _)| *ext == s)
+            .map(|(_, format)| *format)
+            .ok_or_else(|| Error::UnknownConfigFormat(s.to_owned()))
+    }
+}
+
+impl Format {
+    pub(crate) fn from_path(path: &Path) -> Result<Self, Error> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.parse(),
+            None => Err(Error::UnknownConfigFormat(path.display().to_string())),
+        }
+    }
+
+    /// Parse the text of a file, locating errors in it.
+    pub(crate) fn parse<T: DeserializeOwned>(
+        self,
+        path: &Path,
+        text: &str,
+    ) -> Result<T, FileError> {
+        match self {
+            Format::Yaml => serde_yaml::from_str(text).map_err(|e| {
+                let position = e.location().map(|l| (l.line(), l.column()));
+                FileError::new(path, position, e.to_string())
+            }),
+            Format::Json => serde_json::from_str(text).map_err(|e| {
+                let position = Some((e.line(), e.column())).filter(|&(line, _)| line > 0);
+                FileError::new(path, position, e.to_string())
+            }),
+            Format::Toml => toml::from_str(text).map_err(|e| {
+                let position = e.line_col().map(|(line, column)| (line + 1, column + 1));
+                FileError::new(path, position, e.to_string())
+            }),
+        }
+    }
+
+    pub(crate) fn serialize(self, config: &Config) -> Result<String, Error> {
+        Ok(match self {
+            Format::Yaml => serde_yaml::to_string(config)?,
+            Format::Json => serde_json::to_string_pretty(config)? + "\n",
+            // Going through a value puts plain keys before tables, as TOML requires
+            Format::Toml => toml::to_string(&toml::Value::try_from(config)?)?,
+        })
+    }
+}
+
+/// JSON Schema of the configuration files.
+pub(crate) fn schema() -> RootSchema {
+    let mut schema = schemars::schema_for!(Config);
+    crate::action::fix_schema(&mut schema);
+    schema
+}
+
+/// The first `config.<ext>` that exists in a directory.
+fn find(dir: &Path) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|(ext, _)| dir.join(format!("config.{}", ext)))
+        .find(|path| path.exists())
+}
+
+/// The configuration file in a directory: the first `config.<ext>` that
+/// exists, or `config.yaml`.
+pub(crate) fn find_in(dir: &Path) -> PathBuf {
+    find(dir).unwrap_or_else(|| dir.join("config.yaml"))
+}
+
+/// The system-wide configuration files, from `$XDG_CONFIG_DIRS/keytree`, the
+/// least important first.
+pub(crate) fn system_layers() -> Vec<PathBuf> {
+    let dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_owned());
+
+    dirs.split(':')
+        .rev()
+        .filter(|dir| !dir.is_empty())
+        .filter_map(|dir| find(&Path::new(dir).join("keytree")))
+        .collect()
+}
+
+/// An error in a configuration file, at a line and column when known.
+#[derive(Debug)]
+pub struct FileError {
+    path: PathBuf,
+    position: Option<(usize, usize)>,
+    message: String,
+}
+
+impl FileError {
+    fn new(path: &Path, position: Option<(usize, usize)>, message: String) -> Self {
+        // The parsers end their messages with the position, which goes first here
+        let message = match position {
+            Some((line, column)) => message
+                .trim_end_matches(&format!(" at line {} column {}", line, column))
+                .to_owned(),
+            None => message,
+        };
+
+        Self {
+            path: path.to_owned(),
+            position,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path.display(),
+                line,
+                column,
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+/// A configuration file as read, before merging.
+struct File {
+    config: Config,
+    /// Key paths set to null, removing the nodes read before
+    deleted: Vec<Vec<KeyCombination>>,
+    /// Whether the file has a `theme` section
+    has_theme: bool,
+}
+
+/// Collect the key paths of the null entries of a `map` value and of the ones
+/// below it.
+fn find_deleted(
+    map: &serde_json::Value,
+    path: &mut Vec<KeyCombination>,
+    deleted: &mut Vec<Vec<KeyCombination>>,
+) {
+    let entries = match map {
+        serde_json::Value::Object(entries) => entries,
+        _ => return,
+    };
+
+    for (key, node) in entries {
+        path.push(key.clone());
+        match node {
+            serde_json::Value::Null => deleted.push(path.clone()),
+            node => {
+                if let Some(children) = node.get("map") {
+                    find_deleted(children, path, deleted);
+                }
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Whether a YAML file has only comments, and so no document at all.
+pub(crate) fn is_blank(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .all(|l| l.is_empty() || l.starts_with('#'))
+}
+
+fn read(path: &Path) -> Result<File, Error> {
+    let format = Format::from_path(path)?;
+    let mut text = std::fs::read_to_string(path)?;
+    if format == Format::Yaml && is_blank(&text) {
+        text = "{}".to_owned();
+    }
+
+    // The typed configuration leaves out null entries, so they are found in a
+    // generic reading of the file
+    let value: serde_json::Value = format.parse(path, &text)?;
+    let mut deleted = vec![];
+    if let Some(map) = value.get("map") {
+        find_deleted(map, &mut vec![], &mut deleted);
+    }
+    let has_theme = value.get("theme").is_some();
+
+    let mut config: Config = format.parse(path, &text)?;
+
+    // Trees of several files are merged, so the markup default of each file
+    // is made explicit on its own nodes
+    resolve_markup(&mut config.map, config.markup);
+
+    Ok(File {
+        config,
+        deleted,
+        has_theme,
+    })
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new("."))
+}
+
+/// Files named by the `include` entries of a file in `dir`.
+fn resolve_includes(dir: &Path, patterns: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = vec![];
+
+    for pattern in patterns {
+        let path = dir.join(expand_home(Path::new(pattern)));
+        if !pattern.contains(['*', '?', '[']) {
+            paths.push(path);
+            continue;
+        }
+
+        let path = path.to_string_lossy();
+        let matches =
+            glob::glob(&path).map_err(|e| Error::InvalidInclude(pattern.clone(), e.to_string()))?;
+        for entry in matches {
+            paths.push(entry.map_err(std::io::Error::from)?);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Configuration files of the `conf.d` directory next to the main one.
+fn conf_d(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let dir = dir.join("conf.d");
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && Format::from_path(&path).is_ok() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Set `markup` on every node, to the value it inherits when not set.
+fn resolve_markup(map: &mut HashMap<KeyCombination, ActionDesc>, inherited: bool) {
+    for desc in map.values_mut() {
+        let markup = *desc.markup.get_or_insert(inherited);
+        if let Action::Map(children) = &mut desc.action {
+            resolve_markup(children, markup);
+        }
+    }
+}
+
+/// The key of `map` binding the same combinations as `key`, however spelled.
+fn same_key(map: &HashMap<KeyCombination, ActionDesc>, key: &str) -> Option<KeyCombination> {
+    let aliases = combination::aliases(key);
+    map.keys()
+        .find(|k| combination::aliases(k) == aliases)
+        .cloned()
+}
+
+/// The keys of `map` binding some of the combinations of `key`.
+fn overlapping_keys(map: &HashMap<KeyCombination, ActionDesc>, key: &str) -> Vec<KeyCombination> {
+    let aliases = combination::aliases(key);
+    map.keys()
+        .filter(|k| !combination::aliases(k).is_disjoint(&aliases))
+        .cloned()
+        .collect()
+}
+
+/// Remove the combinations of `key` from `map`: nodes bound only by them are
+/// removed, the others keep their other aliases.
+fn unbind(map: &mut HashMap<KeyCombination, ActionDesc>, key: &str) {
+    let aliases = combination::aliases(key);
+
+    for existing in overlapping_keys(map, key) {
+        let desc = match map.remove(&existing) {
+            Some(desc) => desc,
+            None => continue,
+        };
+        let kept: Vec<&str> = existing
+            .split(',')
+            .filter(|alias| !combination::aliases(alias).is_subset(&aliases))
+            .collect();
+        if !kept.is_empty() {
+            map.insert(kept.join(","), desc);
+        }
+    }
+}
+
+/// Remove the node at a key path, if there is one.
+fn delete(map: &mut HashMap<KeyCombination, ActionDesc>, path: &[KeyCombination]) {
+    match path {
+        [] => {}
+        [key] => unbind(map, key),
+        [key, rest @ ..] => {
+            let node = same_key(map, key).and_then(|key| map.get_mut(&key));
+            if let Some(Action::Map(children)) = node.map(|desc| &mut desc.action) {
+                delete(children, rest);
+            }
+        }
+    }
+}
+
+/// Lay a tree over another: nodes of `from` replace those of `into`, except
+/// that `map` nodes present in both are merged.
+fn overlay(
+    into: &mut HashMap<KeyCombination, ActionDesc>,
+    from: HashMap<KeyCombination, ActionDesc>,
+) {
+    for (key, desc) in from {
+        let existing = same_key(into, &key).and_then(|existing| into.get_mut(&existing));
+        match existing {
+            Some(ActionDesc {
+                title,
+                markup,
+                icon,
+                env,
+                cwd,
+                action: Action::Map(existing_map),
+            }) if desc.action.action_map().is_some() => {
+                // Markup is about the title, so they go together
+                if !desc.title.is_empty() {
+                    *title = desc.title;
+                    *markup = desc.markup;
+                }
+                if desc.icon.is_some() {
+                    *icon = desc.icon;
+                }
+                env.extend(desc.env);
+                if desc.cwd.is_some() {
+                    *cwd = desc.cwd;
+                }
+                if let Action::Map(map) = desc.action {
+                    overlay(existing_map, map);
+                }
+            }
+            _ => {
+                unbind(into, &key);
+                into.insert(key, desc);
+            }
+        }
+    }
+}
+
+/// Merges included files into a tree, remembering where each node came from.
+struct Loader {
+    /// Files read so far
+    sources: Vec<PathBuf>,
+    /// Files read for the current layer, to skip those included twice
+    layer_sources: Vec<PathBuf>,
+    /// File defining each node, by key path
+    origins: HashMap<Vec<KeyCombination>, PathBuf>,
+    conflicts: Vec<String>,
+}
+
+impl Loader {
+    /// The file defining a node, or the nearest node above it.
+    fn origin(&self, path: &[KeyCombination]) -> PathBuf {
+        (0..=path.len())
+            .rev()
+            .find_map(|n| self.origins.get(&path[..n]))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn merge(
+        &mut self,
+        into: &mut HashMap<KeyCombination, ActionDesc>,
+        from: HashMap<KeyCombination, ActionDesc>,
+        path: &mut Vec<KeyCombination>,
+        source: &Path,
+    ) {
+        for (key, desc) in from {
+            // The same combinations may be spelled differently in each file,
+            // and only some of them may be bound by the existing key
+            let same = same_key(into, &key);
+            let existing = same.clone().or_else(|| overlapping_keys(into, &key).pop());
+            path.push(existing.clone().unwrap_or_else(|| key.clone()));
+
+            match existing.and_then(|existing| into.get_mut(&existing)) {
+                None => {
+                    self.origins.insert(path.clone(), source.to_owned());
+                    into.insert(key, desc);
+                }
+                Some(existing) => match (&mut existing.action, desc.action) {
+                    (Action::Map(existing_map), Action::Map(map)) if same.is_some() => {
+                        if existing.title.is_empty() {
+                            existing.title = desc.title;
+                        }
+                        self.merge(existing_map, map, path, source);
+                    }
+                    _ => self.conflicts.push(format!(
+                        "{}: defined in {} and in {}",
+                        path.join(" "),
+                        self.origin(path).display(),
+                        source.display()
+                    )),
+                },
+            }
+
+            path.pop();
+        }
+    }
+
+    fn include(
+        &mut self,
+        map: &mut HashMap<KeyCombination, ActionDesc>,
+        path: &Path,
+    ) -> Result<(), Error> {
+        let canonical = path.canonicalize()?;
+        if self.layer_sources.contains(&canonical) {
+            return Ok(());
+        }
+        self.layer_sources.push(canonical.clone());
+        if !self.sources.contains(&canonical) {
+            self.sources.push(canonical);
+        }
+
+        let included = read(path)?;
+        let mut ignored = vec![];
+        if !included.config.settings.is_default() {
+            ignored.push("settings");
+        }
+        if included.has_theme {
+            ignored.push("theme");
+        }
+        if !ignored.is_empty() {
+            log::warn!(
+                "{}: the {} of included files are ignored, only their maps are read",
+                path.display(),
+                ignored.join(" and ")
+            );
+        }
+        for deleted in included.deleted.iter() {
+            delete(map, deleted);
+        }
+
+        let includes = resolve_includes(parent(path), &included.config.include)?;
+        self.merge(map, included.config.map, &mut vec![], path);
+        for include in includes {
+            self.include(map, &include)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a layer, and merge into its map the files it includes and those of
+    /// the `conf.d` directory beside it.
+    fn layer(&mut self, path: &Path) -> Result<File, Error> {
+        let canonical = path.canonicalize()?;
+        self.layer_sources = vec![canonical.clone()];
+        self.sources.push(canonical);
+
+        let mut file = read(path)?;
+        self.origins.clear();
+        self.origins.insert(vec![], path.to_owned());
+
+        let mut includes = resolve_includes(parent(path), &file.config.include)?;
+        includes.extend(conf_d(parent(path))?);
+        for include in includes {
+            self.include(&mut file.config.map, &include)?;
+        }
+
+        Ok(file)
+    }
+}
+
+/// Read configuration layers, from the least important to the most: each one
+/// can add, replace or delete (with a null value) nodes of the tree built by
+/// the layers before it.
+pub(crate) fn load(layers: &[PathBuf]) -> Result<Config, Error> {
+    let mut loader = Loader {
+        sources: vec![],
+        layer_sources: vec![],
+        origins: HashMap::new(),
+        conflicts: vec![],
+    };
+
+    let mut files = vec![];
+    for path in layers {
+        files.push(loader.layer(path)?);
+    }
+
+    if !loader.conflicts.is_empty() {
+        return Err(Error::ConflictingBindings(loader.conflicts.join("\n")));
+    }
+
+    let mut files = files.into_iter();
+    let mut config = match files.next() {
+        Some(file) => file.config,
+        None => return Err(Error::NoConfig),
+    };
+
+    for file in files {
+        config.markup = file.config.markup;
+        config.settings = file.config.settings.or(&config.settings);
+        if file.has_theme {
+            config.theme = file.config.theme;
+        }
+        for deleted in file.deleted.iter() {
+            delete(&mut config.map, deleted);
+        }
+        overlay(&mut config.map, file.config.map);
+    }
+
+    config.include = vec![];
+    config.sources = loader.sources;
+    Ok(config)
+}
+
+/// Expand a leading `~/` in a path to the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_owned(),
+    }
 }
 
 pub fn example() -> Config {
     Config {
+        markup: false,
+        settings: Settings::default(),
+        theme: Theme::default(),
+        include: vec![],
+        sources: vec![],
+        overrides: vec![],
         map: {
             let mut m = HashMap::new();
 
@@ -18,6 +598,10 @@ pub fn example() -> Config {
                 "C-F6".to_owned(),
                 ActionDesc {
                     title: "Main actions".to_owned(),
+                    markup: None,
+                    icon: None,
+                    env: BTreeMap::new(),
+                    cwd: None,
                     action: Action::Map({
                         let mut m = HashMap::new();
 
@@ -25,13 +609,21 @@ pub fn example() -> Config {
                             "r".to_owned(),
                             ActionDesc {
                                 title: "Reload".to_owned(),
-                                action: Action::Reload(()),
+                                markup: None,
+                                icon: None,
+                                env: BTreeMap::new(),
+                                cwd: None,
+                                action: Action::Reload(NoArgs),
                             },
                         );
                         m.insert(
                             "x".to_owned(),
                             ActionDesc {
                                 title: "Sub actions".to_owned(),
+                                markup: None,
+                                icon: None,
+                                env: BTreeMap::new(),
+                                cwd: None,
                                 action: Action::Map({
                                     let mut m = HashMap::new();
 
@@ -39,16 +631,24 @@ pub fn example() -> Config {
                                         "a".to_owned(),
                                         ActionDesc {
                                             title: "Reload".to_owned(),
-                                            action: Action::Reload(()),
+                                            markup: None,
+                                            icon: None,
+                                            env: BTreeMap::new(),
+                                            cwd: None,
+                                            action: Action::Reload(NoArgs),
                                         },
                                     );
                                     m.insert(
                                         "b".to_owned(),
                                         ActionDesc {
                                             title: "Open alacritty".to_owned(),
+                                            markup: None,
+                                            icon: None,
+                                            env: BTreeMap::new(),
+                                            cwd: None,
                                             action: Action::List(vec![
-                                                Op::Execute("alacritty".to_string()),
-                                                Op::Reload(()),
+                                                Op::Execute(Execute::Script("alacritty".to_string())).into(),
+                                                Op::Reload(NoArgs).into(),
                                             ]),
                                         },
                                     );
@@ -56,9 +656,13 @@ pub fn example() -> Config {
                                         "c".to_owned(),
                                         ActionDesc {
                                             title: "Open file manager".to_owned(),
-                                            action: Action::Execute(
+                                            markup: None,
+                                            icon: None,
+                                            env: BTreeMap::new(),
+                                            cwd: None,
+                                            action: Action::Execute(Execute::Script(
                                                 "exo-open --launch FileManager".to_owned(),
-                                            ),
+                                            )),
                                         },
                                     );
 
@@ -70,7 +674,13 @@ pub fn example() -> Config {
                             "e".to_owned(),
                             ActionDesc {
                                 title: "Open file manager".to_owned(),
-                                action: Action::Execute("exo-open --launch FileManager".to_owned()),
+                                markup: None,
+                                icon: None,
+                                env: BTreeMap::new(),
+                                cwd: None,
+                                action: Action::Execute(Execute::Script(
+                                    "exo-open --launch FileManager".to_owned(),
+                                )),
                             },
                         );
 
@@ -82,14 +692,24 @@ pub fn example() -> Config {
                 "F11".to_owned(),
                 ActionDesc {
                     title: "".to_owned(),
-                    action: Action::Execute("exo-open --launch FileManager".to_owned()),
+                    markup: None,
+                    icon: None,
+                    env: BTreeMap::new(),
+                    cwd: None,
+                    action: Action::Execute(Execute::Script(
+                        "exo-open --launch FileManager".to_owned(),
+                    )),
                 },
             );
             m.insert(
                 "C-c".to_owned(),
                 ActionDesc {
                     title: "".to_owned(),
-                    action: Action::Die(()),
+                    markup: None,
+                    icon: None,
+                    env: BTreeMap::new(),
+                    cwd: None,
+                    action: Action::Die(NoArgs),
                 },
             );
 
@@ -97,3 +717,146 @@ pub fn example() -> Config {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::leaf;
+
+    fn node(children: &[(&str, ActionDesc)]) -> ActionDesc {
+        let map = children
+            .iter()
+            .map(|(key, desc)| (key.to_string(), desc.clone()))
+            .collect();
+        ActionDesc {
+            action: Action::Map(map),
+            ..leaf("")
+        }
+    }
+
+    fn tree(children: &[(&str, ActionDesc)]) -> HashMap<KeyCombination, ActionDesc> {
+        match node(children).action {
+            Action::Map(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    fn keys(map: &HashMap<KeyCombination, ActionDesc>) -> Vec<&str> {
+        let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+        keys.sort();
+        keys
+    }
+
+    fn loader() -> Loader {
+        Loader {
+            sources: vec![],
+            layer_sources: vec![],
+            origins: HashMap::new(),
+            conflicts: vec![],
+        }
+    }
+
+    #[test]
+    fn keys_compare_by_combinations() {
+        let map = tree(&[("Control-x,a", leaf("x"))]);
+        assert_eq!(same_key(&map, "a,C-x").as_deref(), Some("Control-x,a"));
+        assert_eq!(same_key(&map, "a"), None);
+        assert_eq!(overlapping_keys(&map, "Ctrl-x"), ["Control-x,a"]);
+    }
+
+    #[test]
+    fn overlay_replaces_other_spellings() {
+        let mut map = tree(&[("Control-x", leaf("old")), ("a,b", leaf("ab"))]);
+        overlay(&mut map, tree(&[("C-x", leaf("new")), ("a", leaf("a"))]));
+
+        assert_eq!(keys(&map), ["C-x", "a", "b"]);
+        assert_eq!(map["C-x"].title, "new");
+        assert_eq!(map["b"].title, "ab");
+    }
+
+    #[test]
+    fn overlay_merges_maps_across_spellings() {
+        let mut map = tree(&[("Super-m", node(&[("a", leaf("a"))]))]);
+        overlay(&mut map, tree(&[("S-m", node(&[("b", leaf("b"))]))]));
+
+        assert_eq!(keys(&map), ["Super-m"]);
+        match &map["Super-m"].action {
+            Action::Map(children) => assert_eq!(keys(children), ["a", "b"]),
+            _ => panic!("not a map"),
+        }
+    }
+
+    #[test]
+    fn delete_any_spelling() {
+        let mut map = tree(&[
+            ("Super-m", node(&[("b,a", leaf("ab")), ("c", leaf("c"))])),
+            ("Control-x", leaf("x")),
+        ]);
+        delete(&mut map, &["C-x".to_owned()]);
+        delete(&mut map, &["S-m".to_owned(), "a".to_owned()]);
+
+        assert_eq!(keys(&map), ["Super-m"]);
+        match &map["Super-m"].action {
+            Action::Map(children) => assert_eq!(keys(children), ["b", "c"]),
+            _ => panic!("not a map"),
+        }
+    }
+
+    #[test]
+    fn includes_conflict_whatever_the_spelling() {
+        let mut map = tree(&[("Control-x", leaf("x")), ("a,b", leaf("ab"))]);
+        let mut loader = loader();
+        let from = tree(&[("C-x", leaf("y")), ("b,a", leaf("ba")), ("b,c", leaf("bc"))]);
+        loader.merge(&mut map, from, &mut vec![], Path::new("other.yaml"));
+
+        assert_eq!(loader.conflicts.len(), 3);
+        assert_eq!(keys(&map), ["Control-x", "a,b"]);
+    }
+
+    #[test]
+    fn includes_merge_maps_whatever_the_spelling() {
+        let mut map = tree(&[("Super-m", node(&[("a", leaf("a"))]))]);
+        let mut loader = loader();
+        let from = tree(&[("S-m", node(&[("b", leaf("b"))]))]);
+        loader.merge(&mut map, from, &mut vec![], Path::new("other.yaml"));
+
+        assert!(loader.conflicts.is_empty());
+        match &map["Super-m"].action {
+            Action::Map(children) => assert_eq!(keys(children), ["a", "b"]),
+            _ => panic!("not a map"),
+        }
+    }
+
+    #[test]
+    fn layers_include_the_same_file() {
+        let dir = std::env::temp_dir().join(format!("keytree-layers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("shared.yaml", "map: {s: {execute: shared}}"),
+            (
+                "system.yaml",
+                "include: [shared.yaml]\nmap: {a: {execute: a}}",
+            ),
+            ("local.yaml", "map: {s: {execute: local}}"),
+            (
+                "user.yaml",
+                "include: [shared.yaml]\nmap: {b: {execute: b}}",
+            ),
+        ];
+        for (name, text) in files.iter() {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+
+        let layers: Vec<PathBuf> = files[1..].iter().map(|(name, _)| dir.join(name)).collect();
+        let config = load(&layers);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(keys(&config.map), ["a", "b", "s"]);
+        match &config.map["s"].action {
+            Action::Execute(Execute::Script(script)) => assert_eq!(script, "shared"),
+            action => panic!("{:?}", action),
+        }
+        assert_eq!(config.sources.len(), 4);
+    }
+}
@@ -8,12 +8,36 @@ pub(crate) enum Error {
     #[error("Fmt error; {0}")]
     FmtError(#[from] std::fmt::Error),
 
-    #[error("Config error: {0}")]
-    ConfigError(#[from] config::ConfigError),
+    #[error("{0}")]
+    ConfigError(#[from] crate::config::FileError),
 
     #[error("Serde yaml error; {0}")]
     SerdeYAMLError(#[from] serde_yaml::Error),
 
+    #[error("Serde json error; {0}")]
+    SerdeJSONError(#[from] serde_json::Error),
+
+    #[error("Toml error; {0}")]
+    TomlError(#[from] toml::ser::Error),
+
+    #[error("Unknown configuration format: {0} (expected .yaml, .yml, .json or .toml)")]
+    UnknownConfigFormat(String),
+
+    #[error("Invalid include pattern {0}: {1}")]
+    InvalidInclude(String, String),
+
+    #[error("Invalid override {0}: {1}")]
+    InvalidOverride(String, String),
+
+    #[error("Invalid aliases: {0} (expected keep, collapse or expand)")]
+    InvalidAliases(String),
+
+    #[error("Not in canonical form: {0}")]
+    NotFormatted(String),
+
+    #[error("Conflicting bindings:\n{0}")]
+    ConflictingBindings(String),
+
     #[error("ParseInt error: {0}")]
     ParseIntError(#[from] std::num::ParseIntError),
 
@@ -23,9 +47,36 @@ pub(crate) enum Error {
     #[error("No screen found")]
     NoScreenFound,
 
+    #[error("No usable visual found")]
+    NoVisual,
+
+    #[error("Invalid monitor in {0}, line {1}: {2}")]
+    InvalidMonitorCache(String, usize, serde_json::Error),
+
     #[error("Invalid position specified")]
     InvalidPosition,
 
+    #[error("Invalid placement: {0} (expected largest, primary, pointer, focused or fixed)")]
+    InvalidPlacement(String),
+
+    #[error("Invalid log level: {0} (expected off, error, warn, info, debug or trace)")]
+    InvalidLogLevel(String),
+
+    #[error("Invalid anchor: {0}")]
+    InvalidAnchor(String),
+
+    #[error("Render error: {0}")]
+    Render(String),
+
+    #[error("Unknown output format: {0}")]
+    UnknownOutputFormat(String),
+
+    #[error("Unknown import format: {0} (expected sxhkd or xbindkeys)")]
+    UnknownImportFormat(String),
+
+    #[error("No such node: {0}")]
+    NoSuchNode(String),
+
     #[error("Unknown key: {0}")]
     UnknownKey(String),
 
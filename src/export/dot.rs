@@ -0,0 +1,116 @@
+use std::io::Write;
+
+use super::{walk, Node, Visitor};
+use crate::config::Config;
+use crate::error::Error;
+
+/// Quote a string for use as a DOT identifier or label.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Label of the edge leading to a node. An alias list such as `a,b` is a
+/// single edge, with one line per alias.
+fn edge_label(node: &Node) -> String {
+    let key = node.path.last().map(|k| k.as_str()).unwrap_or("");
+    let aliases: Vec<String> = key
+        .split(',')
+        .map(|alias| alias.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect();
+    format!("\"{}\"", aliases.join("\\n"))
+}
+
+struct Dot {
+    out: Box<dyn Write>,
+    /// Identifiers of the `map` nodes above the current one
+    parents: Vec<String>,
+    next_id: usize,
+}
+
+impl Dot {
+    fn indent(&self) -> String {
+        "  ".repeat(self.parents.len() + 1)
+    }
+
+    /// Declare a node in the current cluster, with an edge from its parent.
+    fn node(&mut self, node: &Node, parent: &str, shape: &str) -> Result<String, Error> {
+        let id = format!("n{}", self.next_id);
+        self.next_id += 1;
+
+        let title = node.plain_title();
+        let label = if title.is_empty() { node.keys() } else { title };
+        let indent = self.indent();
+        writeln!(
+            self.out,
+            "{}{} [label={}, shape={}];",
+            indent,
+            id,
+            quote(&label),
+            shape
+        )?;
+        writeln!(
+            self.out,
+            "{}{} -> {} [label={}];",
+            indent,
+            parent,
+            id,
+            edge_label(node)
+        )?;
+
+        Ok(id)
+    }
+
+    fn parent(&self) -> String {
+        self.parents
+            .last()
+            .cloned()
+            .unwrap_or_else(|| "root".to_owned())
+    }
+}
+
+impl Visitor for Dot {
+    fn enter(&mut self, node: &Node) -> Result<(), Error> {
+        let parent = self.parent();
+        writeln!(
+            self.out,
+            "{}subgraph cluster_{} {{",
+            self.indent(),
+            self.next_id
+        )?;
+
+        // The map node itself is drawn inside its cluster
+        self.parents.push(String::new());
+        writeln!(self.out, "{}label={};", self.indent(), quote(&node.keys()))?;
+        let id = self.node(node, &parent, "box")?;
+        *self.parents.last_mut().unwrap() = id;
+        Ok(())
+    }
+
+    fn leave(&mut self, _node: &Node) -> Result<(), Error> {
+        self.parents.pop();
+        writeln!(self.out, "{}}}", self.indent())?;
+        Ok(())
+    }
+
+    fn leaf(&mut self, node: &Node) -> Result<(), Error> {
+        let parent = self.parent();
+        self.node(node, &parent, "ellipse")?;
+        Ok(())
+    }
+}
+
+pub(crate) fn export(config: &Config, mut out: Box<dyn Write>) -> Result<(), Error> {
+    writeln!(out, "digraph keytree {{")?;
+    writeln!(out, "  rankdir=LR;")?;
+    writeln!(out, "  root [label=\"keytree\", shape=doublecircle];")?;
+
+    let mut dot = Dot {
+        out,
+        parents: vec![],
+        next_id: 0,
+    };
+    walk(config, &mut dot)?;
+    writeln!(dot.out, "}}")?;
+    dot.out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::io::Write;
+
+use super::{walk, Node, Visitor};
+use crate::config::Config;
+use crate::error::Error;
+use crate::layout::escape_markup;
+
+const HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>keytree bindings</title>
+<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+td.group { font-weight: bold; background: #eee; }
+</style>
+</head>
+<body>
+<table>
+<tr><th>Keys</th><th>Title</th><th>Action</th></tr>
+"#;
+
+const FOOTER: &str = "</table>\n</body>\n</html>\n";
+
+struct Html {
+    out: Box<dyn Write>,
+}
+
+impl Visitor for Html {
+    fn enter(&mut self, node: &Node) -> Result<(), Error> {
+        writeln!(
+            self.out,
+            "<tr><td class=\"group\"><code>{}</code></td><td class=\"group\" colspan=\"2\">{}</td></tr>",
+            escape_markup(&node.keys()),
+            escape_markup(&node.plain_title())
+        )?;
+        Ok(())
+    }
+
+    fn leaf(&mut self, node: &Node) -> Result<(), Error> {
+        writeln!(
+            self.out,
+            "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td></tr>",
+            escape_markup(&node.keys()),
+            escape_markup(&node.plain_title()),
+            escape_markup(&node.action_text())
+        )?;
+        Ok(())
+    }
+}
+
+pub(crate) fn export(config: &Config, mut out: Box<dyn Write>) -> Result<(), Error> {
+    out.write_all(HEADER.as_bytes())?;
+
+    let mut html = Html { out };
+    walk(config, &mut html)?;
+    html.out.write_all(FOOTER.as_bytes())?;
+    html.out.flush()?;
+    Ok(())
+}
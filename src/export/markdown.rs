@@ -0,0 +1,37 @@
+use std::io::Write;
+
+use super::{walk, Node, Visitor};
+use crate::config::Config;
+use crate::error::Error;
+
+/// Escape a table cell, so that pipes don't start a new column.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+struct Markdown {
+    out: Box<dyn Write>,
+}
+
+impl Visitor for Markdown {
+    fn leaf(&mut self, node: &Node) -> Result<(), Error> {
+        writeln!(
+            self.out,
+            "| `{}` | {} | `{}` |",
+            cell(&node.keys()),
+            cell(&node.plain_title()),
+            cell(&node.action_text())
+        )?;
+        Ok(())
+    }
+}
+
+pub(crate) fn export(config: &Config, mut out: Box<dyn Write>) -> Result<(), Error> {
+    writeln!(out, "| Keys | Title | Action |")?;
+    writeln!(out, "| ---- | ----- | ------ |")?;
+
+    let mut markdown = Markdown { out };
+    walk(config, &mut markdown)?;
+    markdown.out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::action::{ActionDesc, Context, Step};
+use crate::combination::KeyCombination;
+use crate::config::Config;
+use crate::error::Error;
+use crate::layout::strip_markup;
+
+mod dot;
+mod html;
+mod markdown;
+mod sheet;
+mod sxhkd;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Markdown,
+    Html,
+    Pdf,
+    Svg,
+    Dot,
+    Sxhkd,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "markdown" | "md" => Format::Markdown,
+            "html" => Format::Html,
+            "pdf" => Format::Pdf,
+            "svg" => Format::Svg,
+            "dot" => Format::Dot,
+            "sxhkd" => Format::Sxhkd,
+            _ => return Err(Error::UnknownOutputFormat(s.to_owned())),
+        })
+    }
+}
+
+/// A node of the tree, as seen while walking it.
+pub(crate) struct Node<'a> {
+    /// Keys leading to the node, the last one being the node's own
+    pub path: Vec<&'a KeyCombination>,
+    pub desc: &'a ActionDesc,
+    /// Whether the title is Pango markup, possibly inherited from the nodes above
+    pub markup: bool,
+    /// Environment and working directory of the node's commands
+    pub context: Context,
+}
+
+impl<'a> Node<'a> {
+    /// The key path as typed, for example `Menu s c`
+    pub(crate) fn keys(&self) -> String {
+        let keys: Vec<&str> = self.path.iter().map(|k| k.as_str()).collect();
+        keys.join(" ")
+    }
+
+    /// The title with any Pango markup removed
+    pub(crate) fn plain_title(&self) -> String {
+        if self.markup {
+            strip_markup(&self.desc.title)
+        } else {
+            self.desc.title.clone()
+        }
+    }
+
+    /// What the node runs, in the environment of the nodes above
+    pub(crate) fn steps(&self) -> Vec<Step> {
+        let steps = self.desc.action.to_steps();
+        steps.iter().map(|step| step.resolve(&self.context)).collect()
+    }
+
+    /// What the node does, as a single line
+    pub(crate) fn action_text(&self) -> String {
+        let ops: Vec<String> = self.steps().iter().map(|step| step.op.to_string()).collect();
+        ops.join("; ")
+    }
+}
+
+/// Receives the nodes of the tree in depth-first order, with siblings sorted
+/// by key.
+pub(crate) trait Visitor {
+    /// A `map` node, before its children
+    fn enter(&mut self, _node: &Node) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// A `map` node, after its children
+    fn leave(&mut self, _node: &Node) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Any other node
+    fn leaf(&mut self, _node: &Node) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+fn walk_map<'a>(
+    map: &'a HashMap<KeyCombination, ActionDesc>,
+    path: &mut Vec<&'a KeyCombination>,
+    markup: bool,
+    context: &Context,
+    visitor: &mut dyn Visitor,
+) -> Result<(), Error> {
+    let mut keys: Vec<_> = map.keys().collect();
+    keys.sort();
+
+    for key in keys {
+        let desc = &map[key];
+        path.push(key);
+
+        let node = Node {
+            path: path.clone(),
+            desc,
+            markup: desc.markup.unwrap_or(markup),
+            context: context.enter(desc),
+        };
+
+        if let Some(children) = desc.action.action_map() {
+            visitor.enter(&node)?;
+            walk_map(children, path, node.markup, &node.context, visitor)?;
+            visitor.leave(&node)?;
+        } else {
+            visitor.leaf(&node)?;
+        }
+
+        path.pop();
+    }
+
+    Ok(())
+}
+
+/// Visit every node of the tree.
+pub(crate) fn walk(config: &Config, visitor: &mut dyn Visitor) -> Result<(), Error> {
+    walk_map(&config.map, &mut vec![], config.markup, &Context::default(), visitor)
+}
+
+/// A leaf of the tree: the full key path leading to it, and what it runs.
+pub(crate) struct Binding {
+    pub path: Vec<KeyCombination>,
+    pub title: String,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Default)]
+struct Table {
+    bindings: Vec<Binding>,
+}
+
+impl Visitor for Table {
+    fn leaf(&mut self, node: &Node) -> Result<(), Error> {
+        self.bindings.push(Binding {
+            path: node.path.iter().map(|k| (*k).clone()).collect(),
+            title: node.plain_title(),
+            steps: node.steps(),
+        });
+        Ok(())
+    }
+}
+
+/// The flattened tree, as a table from key paths to operations, sorted by path.
+pub(crate) fn bindings(config: &Config) -> Result<Vec<Binding>, Error> {
+    let mut table = Table::default();
+    walk(config, &mut table)?;
+    Ok(table.bindings)
+}
+
+/// Write the whole tree in the given format, returning the nodes that the
+/// format could not express.
+pub(crate) fn export(
+    config: &Config,
+    format: Format,
+    out: Box<dyn Write>,
+    font: &str,
+) -> Result<Vec<String>, Error> {
+    match format {
+        Format::Markdown => markdown::export(config, out)?,
+        Format::Html => html::export(config, out)?,
+        Format::Dot => dot::export(config, out)?,
+        Format::Pdf | Format::Svg => sheet::export(config, format, out, font)?,
+        Format::Sxhkd => return sxhkd::export(config, out),
+    }
+
+    Ok(vec![])
+}
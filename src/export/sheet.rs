@@ -0,0 +1,193 @@
+use std::io::Write;
+
+use cairo::{Context, Format as ImageFormat, ImageSurface, PdfSurface, Surface, SvgSurface};
+use pango::FontDescription;
+
+use super::{walk, Format, Node, Visitor};
+use crate::config::Config;
+use crate::error::Error;
+use crate::layout::escape_markup;
+use crate::leechbar::component::text;
+
+/// A4 landscape, in points
+const PAGE_WIDTH: f64 = 842.;
+const PAGE_HEIGHT: f64 = 595.;
+const MARGIN: f64 = 36.;
+const COLUMNS: usize = 3;
+const COLUMN_GAP: f64 = 18.;
+/// Indentation of each level of the tree
+const INDENT: f64 = 10.;
+/// Extra space above each top-level group
+const GROUP_GAP: f64 = 6.;
+
+/// A line of the sheet, in Pango markup, indented by its depth in the tree.
+struct Line {
+    depth: usize,
+    markup: String,
+}
+
+#[derive(Default)]
+struct Lines {
+    lines: Vec<Line>,
+}
+
+impl Visitor for Lines {
+    fn enter(&mut self, node: &Node) -> Result<(), Error> {
+        let key = escape_markup(node.path.last().map(|k| k.as_str()).unwrap_or(""));
+        let title = escape_markup(&node.plain_title());
+        self.lines.push(Line {
+            depth: node.path.len() - 1,
+            markup: format!("<b>{}  {}</b>", key, title),
+        });
+        Ok(())
+    }
+
+    fn leaf(&mut self, node: &Node) -> Result<(), Error> {
+        let key = escape_markup(node.path.last().map(|k| k.as_str()).unwrap_or(""));
+        let title = escape_markup(&node.plain_title());
+        let action = escape_markup(&node.action_text());
+        self.lines.push(Line {
+            depth: node.path.len() - 1,
+            markup: format!(
+                "<tt>{}</tt>  {}  <span foreground=\"#777777\"><small><tt>{}</tt></small></span>",
+                key, title, action
+            ),
+        });
+        Ok(())
+    }
+}
+
+/// A line laid out at its final size, waiting to be placed.
+struct Block {
+    indent: f64,
+    space_before: f64,
+    height: f64,
+    layout: pango::Layout,
+}
+
+/// Position of a block: page, column and vertical offset inside the column.
+type Place = (usize, usize, f64);
+
+/// Fill columns of `column_height` from top to bottom, then pages of `COLUMNS` columns.
+fn arrange(blocks: &[Block], column_height: f64) -> Vec<Place> {
+    let mut places = Vec::with_capacity(blocks.len());
+    let (mut page, mut column, mut y) = (0, 0, 0.);
+
+    for block in blocks {
+        let mut space_before = if y > 0. { block.space_before } else { 0. };
+        if y > 0. && y + space_before + block.height > column_height {
+            column += 1;
+            y = 0.;
+            space_before = 0.;
+            if column == COLUMNS {
+                page += 1;
+                column = 0;
+            }
+        }
+
+        places.push((page, column, y + space_before));
+        y += space_before + block.height;
+    }
+
+    places
+}
+
+fn stream_error(e: cairo::StreamWithError) -> Error {
+    Error::Render(e.error.to_string())
+}
+
+fn draw_page(
+    context: &Context,
+    blocks: &[Block],
+    places: &[Place],
+    page: usize,
+    column_width: f64,
+) {
+    context.set_source_rgb(0., 0., 0.);
+    for (block, (_, column, y)) in blocks.iter().zip(places).filter(|(_, p)| p.0 == page) {
+        let x = MARGIN + *column as f64 * (column_width + COLUMN_GAP) + block.indent;
+        context.move_to(x, MARGIN + y);
+        pangocairo::functions::update_layout(context, &block.layout);
+        pangocairo::functions::show_layout(context, &block.layout);
+    }
+}
+
+/// Draw the tree as a multi-column cheat sheet: pages of A4 landscape for PDF,
+/// or a single page tall enough to hold balanced columns for SVG.
+pub(crate) fn export(
+    config: &Config,
+    format: Format,
+    out: Box<dyn Write>,
+    font: &str,
+) -> Result<(), Error> {
+    let mut lines = Lines::default();
+    walk(config, &mut lines)?;
+
+    let font = FontDescription::from_string(font);
+    let column_width =
+        (PAGE_WIDTH - 2. * MARGIN - (COLUMNS - 1) as f64 * COLUMN_GAP) / COLUMNS as f64;
+
+    // Lay out every line once, wrapping it to the width left by its indentation
+    let measure = ImageSurface::create(ImageFormat::ARgb32, 0, 0)
+        .map_err(|e| Error::Render(format!("{:?}", e)))?;
+    let measure = Context::new(&measure);
+    let blocks: Vec<Block> = lines
+        .lines
+        .iter()
+        .map(|line| {
+            let indent = line.depth as f64 * INDENT;
+            let layout = text::layout(&measure, &line.markup, &font, true);
+            layout.set_width(((column_width - indent) * f64::from(pango::SCALE)) as i32);
+            layout.set_wrap(pango::WrapMode::WordChar);
+            let (_, height) = layout.get_pixel_size();
+
+            Block {
+                indent,
+                space_before: if line.depth == 0 { GROUP_GAP } else { 0. },
+                height: f64::from(height),
+                layout,
+            }
+        })
+        .collect();
+
+    match format {
+        Format::Pdf => {
+            let places = arrange(&blocks, PAGE_HEIGHT - 2. * MARGIN);
+            let pages = places.last().map(|p| p.0 + 1).unwrap_or(1);
+            let surface = PdfSurface::for_stream(PAGE_WIDTH, PAGE_HEIGHT, out)
+                .map_err(|e| Error::Render(format!("{:?}", e)))?;
+            let context = Context::new(&surface);
+
+            for page in 0..pages {
+                draw_page(&context, &blocks, &places, page, column_width);
+                context.show_page();
+            }
+            finish(&surface)
+        }
+        _ => {
+            // Grow the columns until everything fits in a single page
+            let total: f64 = blocks.iter().map(|b| b.space_before + b.height).sum();
+            let step = blocks.iter().map(|b| b.height).fold(1., f64::max);
+            let mut column_height = (total / COLUMNS as f64).max(step);
+            let mut places = arrange(&blocks, column_height);
+            while places.last().map(|p| p.0 > 0).unwrap_or(false) {
+                column_height += step;
+                places = arrange(&blocks, column_height);
+            }
+
+            let surface = SvgSurface::for_stream(PAGE_WIDTH, column_height + 2. * MARGIN, out)
+                .map_err(|e| Error::Render(format!("{:?}", e)))?;
+            let context = Context::new(&surface);
+            draw_page(&context, &blocks, &places, 0, column_width);
+            finish(&surface)
+        }
+    }
+}
+
+fn finish(surface: &Surface) -> Result<(), Error> {
+    let stream = surface.finish_output_stream().map_err(stream_error)?;
+    if let Ok(mut out) = stream.downcast::<Box<dyn Write>>() {
+        out.flush()?;
+    }
+    Ok(())
+}
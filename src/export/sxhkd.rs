@@ -0,0 +1,250 @@
+use std::io::Write;
+
+use super::bindings;
+use crate::action::{shell_quote, OnFailure, Op, Step};
+use crate::combination::Combination;
+use crate::config::Config;
+use crate::error::Error;
+
+/// Translate a combination to an sxhkd chord such as `ctrl + super + a`.
+fn chord(combination: &str) -> Result<String, String> {
+    let combination = Combination::parse(combination).map_err(|e| e.to_string())?;
+    let mut keys = vec![];
+
+    if combination.modifiers.control {
+        keys.push("ctrl".to_owned());
+    }
+    if combination.modifiers.superr {
+        keys.push("super".to_owned());
+    }
+    if combination.modifiers.meta {
+        keys.push("meta".to_owned());
+    }
+    if combination.modifiers.alt {
+        keys.push("alt".to_owned());
+    }
+    if combination.modifiers.hyper {
+        keys.push("hyper".to_owned());
+    }
+
+    // keytree only sees the unshifted symbol of a key, so it never fires
+    // bindings of upper case letters
+    let name = crate::keysym::sym_to_name(combination.key);
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_uppercase() {
+            return Err(format!("upper case {} is never typed in keytree", name));
+        }
+    }
+    keys.push(name.to_string());
+
+    Ok(keys.join(" + "))
+}
+
+/// All the chord chains of a path, one per combination of its aliases.
+fn chains(path: &[String]) -> Result<Vec<String>, String> {
+    let mut chains = vec![String::new()];
+
+    for key in path {
+        let mut next = vec![];
+        for alias in key.split(',') {
+            let chord = chord(alias)?;
+            for chain in chains.iter() {
+                if chain.is_empty() {
+                    next.push(chord.clone());
+                } else {
+                    next.push(format!("{} ; {}", chain, chord));
+                }
+            }
+        }
+        chains = next;
+    }
+
+    Ok(chains)
+}
+
+/// A shell command running an operation, stopped after `timeout` milliseconds.
+fn op_command(op: &Op, timeout: Option<u64>) -> Result<String, String> {
+    let script = match op {
+        Op::Execute(e) => e.to_shell(),
+        Op::Reload(_) | Op::Die(_) | Op::Show(_) => {
+            return Err(format!("`{}` has no sxhkd equivalent", op))
+        }
+    };
+    if script.contains('\n') {
+        return Err("multi-line commands can't be written for sxhkd".to_owned());
+    }
+
+    Ok(match timeout {
+        Some(ms) => format!("timeout {}s sh -c {}", ms as f64 / 1000.0, shell_quote(&script)),
+        None => script,
+    })
+}
+
+/// A shell command running the steps of a list, as keytree does: each one
+/// after the previous one has finished.
+fn command(steps: &[Step]) -> Result<String, String> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return Ok(String::new()),
+    };
+
+    let first = op_command(&step.op, step.timeout)?;
+    if rest.is_empty() {
+        return Ok(match &step.on_failure {
+            OnFailure::Run(op) => format!("({}) || ({})", first, op_command(op, None)?),
+            _ => first,
+        });
+    }
+
+    // The rest is grouped for the operators to apply to all of it, with
+    // braces for lists, since `((` starts arithmetic in some shells
+    let then = match rest {
+        [next] if !matches!(next.on_failure, OnFailure::Run(_)) => format!("({})", command(rest)?),
+        _ => format!("{{ {}; }}", command(rest)?),
+    };
+
+    Ok(match &step.on_failure {
+        OnFailure::Stop => format!("({}) && {}", first, then),
+        OnFailure::Continue => format!("({}); {}", first, then),
+        OnFailure::Run(op) => format!(
+            "if ({}); then {}; else ({}); fi",
+            first,
+            then,
+            op_command(op, None)?
+        ),
+    })
+}
+
+pub(crate) fn export(config: &Config, mut out: Box<dyn Write>) -> Result<Vec<String>, Error> {
+    let mut unsupported = vec![];
+    writeln!(out, "# Generated by keytree export --format sxhkd")?;
+
+    for binding in bindings(config)? {
+        let keys = binding.path.join(" ");
+        let (command, chains) = match (command(&binding.steps), chains(&binding.path)) {
+            (Ok(command), Ok(chains)) => (command, chains),
+            (Err(reason), _) | (_, Err(reason)) => {
+                unsupported.push(format!("{}: {}", keys, reason));
+                continue;
+            }
+        };
+
+        for chain in chains {
+            writeln!(out)?;
+            if !binding.title.is_empty() {
+                writeln!(out, "# {}", binding.title.replace('\n', " "))?;
+            }
+            writeln!(out, "{}", chain)?;
+            writeln!(out, "\t{}", command)?;
+        }
+    }
+
+    out.flush()?;
+    Ok(unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::ActionDesc;
+
+    fn steps(yaml: &str) -> Vec<Step> {
+        serde_yaml::from_str::<ActionDesc>(yaml)
+            .unwrap()
+            .action
+            .to_steps()
+    }
+
+    #[test]
+    fn chords() {
+        assert_eq!(chord("C-S-a").unwrap(), "ctrl + super + a");
+        assert_eq!(chord("Return").unwrap(), "Return");
+        assert!(chord("C-Q").is_err());
+        assert_eq!(
+            chains(&["S-a,S-b".to_owned(), "c".to_owned()]).unwrap(),
+            ["super + a ; c", "super + b ; c"]
+        );
+    }
+
+    #[test]
+    fn single_step() {
+        assert_eq!(command(&steps("execute: a; b")).unwrap(), "a; b");
+        assert_eq!(
+            command(&steps("list: [{execute: a, on_failure: {execute: b; c}}]")).unwrap(),
+            "(a) || (b; c)"
+        );
+    }
+
+    #[test]
+    fn stop_on_failure() {
+        assert_eq!(
+            command(&steps("list: [execute: a, execute: b]")).unwrap(),
+            "(a) && (b)"
+        );
+        assert_eq!(
+            command(&steps(
+                "list: [execute: a, {execute: b, on_failure: {execute: c}}]"
+            ))
+            .unwrap(),
+            "(a) && { (b) || (c); }"
+        );
+        assert_eq!(
+            command(&steps(
+                "list: [execute: a, {execute: b, on_failure: continue}, execute: c]"
+            ))
+            .unwrap(),
+            "(a) && { (b); (c); }"
+        );
+    }
+
+    #[test]
+    fn continue_on_failure() {
+        assert_eq!(
+            command(&steps(
+                "list: [{execute: a, on_failure: continue}, execute: b]"
+            ))
+            .unwrap(),
+            "(a); (b)"
+        );
+        assert_eq!(
+            command(&steps(
+                "list: [{execute: a, on_failure: continue}, execute: b, execute: c]"
+            ))
+            .unwrap(),
+            "(a); { (b) && (c); }"
+        );
+    }
+
+    #[test]
+    fn run_on_failure() {
+        assert_eq!(
+            command(&steps(
+                "list: [{execute: a, on_failure: {execute: c}}, execute: b]"
+            ))
+            .unwrap(),
+            "if (a); then (b); else (c); fi"
+        );
+        assert_eq!(
+            command(&steps(
+                "list: [{execute: a, on_failure: {execute: c}}, execute: b, execute: d]"
+            ))
+            .unwrap(),
+            "if (a); then { (b) && (d); }; else (c); fi"
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        assert_eq!(
+            command(&steps("list: [{execute: a; b, timeout: 1500}, execute: c]")).unwrap(),
+            "(timeout 1.5s sh -c 'a; b') && (c)"
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        assert!(command(&steps("list: [execute: a, reload: true]")).is_err());
+        assert!(command(&steps("execute: \"a\\nb\"")).is_err());
+    }
+}
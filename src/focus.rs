@@ -0,0 +1,126 @@
+//! The application window that had the focus before keytree took it, so that
+//! commands can act on it.
+
+use xcb::Connection;
+
+use crate::action::Context;
+use crate::error::Error;
+
+/// Longest property read, in 32-bit units
+const PROPERTY_LENGTH: u32 = 1024;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FocusedWindow {
+    pub id: xcb::Window,
+    /// Instance and class names, from `WM_CLASS`
+    pub instance: Option<String>,
+    pub class: Option<String>,
+    /// Process owning the window, from `_NET_WM_PID`
+    pub pid: Option<u32>,
+    /// Title, from `_NET_WM_NAME` or else `WM_NAME`
+    pub title: Option<String>,
+}
+
+impl FocusedWindow {
+    /// The application window holding the focus `focus`: the first one with a
+    /// `WM_CLASS`, from the focused window up to the root.
+    pub(crate) fn find(conn: &Connection, focus: xcb::Window) -> Result<Option<Self>, Error> {
+        if focus == xcb::NONE || focus == xcb::INPUT_FOCUS_POINTER_ROOT {
+            return Ok(None);
+        }
+
+        let mut window = focus;
+        let wm_class = loop {
+            if let Some(wm_class) = text_property(conn, window, xcb::ATOM_WM_CLASS)? {
+                break Some(wm_class);
+            }
+
+            let tree = xcb::query_tree(conn, window).get_reply()?;
+            if tree.parent() == xcb::NONE || tree.parent() == tree.root() {
+                // No application window above, describe the focused one
+                window = focus;
+                break None;
+            }
+            window = tree.parent();
+        };
+
+        let mut names = wm_class.iter().flat_map(|wm_class| wm_class.split('\0'));
+        let instance = names.next().map(str::to_owned);
+        let class = names.next().map(str::to_owned);
+
+        let pid = match atom(conn, "_NET_WM_PID")? {
+            Some(pid) => cardinal_property(conn, window, pid)?,
+            None => None,
+        };
+        let title = match atom(conn, "_NET_WM_NAME")? {
+            Some(name) => text_property(conn, window, name)?,
+            None => None,
+        };
+        let title = match title {
+            Some(title) => Some(title),
+            None => text_property(conn, window, xcb::ATOM_WM_NAME)?,
+        };
+
+        Ok(Some(FocusedWindow {
+            id: window,
+            instance,
+            class,
+            pid,
+            title,
+        }))
+    }
+}
+
+/// Give the window to the commands of a context, as the `window`,
+/// `window_class`, `window_instance`, `window_pid` and `window_title`
+/// variables, which are empty without a window.
+pub(crate) fn set_vars(context: &mut Context, window: Option<&FocusedWindow>) {
+    let window = window.cloned().unwrap_or_default();
+    let vars = [
+        ("window", Some(window.id).filter(|&id| id != xcb::NONE).map(|id| id.to_string())),
+        ("window_class", window.class),
+        ("window_instance", window.instance),
+        ("window_pid", window.pid.map(|pid| pid.to_string())),
+        ("window_title", window.title),
+    ];
+    for (name, value) in vars.iter() {
+        context.set_var(name, value.as_deref().unwrap_or_default());
+    }
+}
+
+/// An atom known to the server, without creating it.
+fn atom(conn: &Connection, name: &str) -> Result<Option<xcb::Atom>, Error> {
+    let atom = xcb::intern_atom(conn, true, name).get_reply()?.atom();
+    Ok(Some(atom).filter(|&atom| atom != xcb::ATOM_NONE))
+}
+
+/// A string property, of any encoding, without its final null.
+fn text_property(
+    conn: &Connection,
+    window: xcb::Window,
+    property: xcb::Atom,
+) -> Result<Option<String>, Error> {
+    let reply = xcb::get_property(conn, false, window, property, xcb::ATOM_ANY, 0, PROPERTY_LENGTH)
+        .get_reply()?;
+    if reply.format() != 8 || reply.value_len() == 0 {
+        return Ok(None);
+    }
+
+    let value: &[u8] = reply.value();
+    let value = value.strip_suffix(b"\0").unwrap_or(value);
+    Ok(Some(String::from_utf8_lossy(value).into_owned()))
+}
+
+fn cardinal_property(
+    conn: &Connection,
+    window: xcb::Window,
+    property: xcb::Atom,
+) -> Result<Option<u32>, Error> {
+    let reply = xcb::get_property(conn, false, window, property, xcb::ATOM_CARDINAL, 0, 1)
+        .get_reply()?;
+    if reply.format() != 32 {
+        return Ok(None);
+    }
+
+    Ok(reply.value::<u32>().first().copied())
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::action::{Action, ActionDesc, Execute};
+use crate::combination::{Combination, KeyCombination};
+use crate::config::Config;
+use crate::error::Error;
+use crate::settings::Settings;
+use crate::theme::Theme;
+
+mod sxhkd;
+mod xbindkeys;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Source {
+    Sxhkd,
+    Xbindkeys,
+}
+
+impl FromStr for Source {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "sxhkd" => Source::Sxhkd,
+            "xbindkeys" => Source::Xbindkeys,
+            _ => return Err(Error::UnknownImportFormat(s.to_owned())),
+        })
+    }
+}
+
+/// A binding read from another tool, with its keys already translated.
+pub(crate) struct Binding {
+    pub line: usize,
+    /// Combinations to type one after the other
+    pub chain: Vec<KeyCombination>,
+    pub title: String,
+    pub command: String,
+}
+
+/// An entry which could not be imported, and why.
+pub(crate) struct Skipped {
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}: {}", self.line, self.text, self.reason)
+    }
+}
+
+/// What a parser made of a file.
+#[derive(Default)]
+pub(crate) struct Parsed {
+    pub bindings: Vec<Binding>,
+    pub skipped: Vec<Skipped>,
+}
+
+/// Translate a key and the modifiers held with it to a keytree combination.
+///
+/// keytree has no shift modifier and only sees the unshifted symbol of a key,
+/// so shift and upper case letters have no equivalent.
+pub(crate) fn combination(modifiers: &[&str], key: &str) -> Result<KeyCombination, String> {
+    let mut prefix = String::new();
+
+    for modifier in modifiers {
+        match modifier.to_lowercase().as_str() {
+            "control" | "ctrl" => prefix.push_str("C-"),
+            "super" | "mod4" => prefix.push_str("S-"),
+            "meta" => prefix.push_str("M-"),
+            "alt" | "mod1" => prefix.push_str("A-"),
+            "hyper" => prefix.push_str("H-"),
+            "shift" => return Err("shift has no keytree equivalent".to_owned()),
+            other => return Err(format!("unsupported modifier {}", other)),
+        }
+    }
+
+    if key.to_lowercase().starts_with("button") {
+        return Err("mouse buttons are not supported".to_owned());
+    }
+
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_uppercase() {
+            return Err(format!("upper case {} has no keytree equivalent", key));
+        }
+    }
+
+    Combination::parse(&format!("{}{}", prefix, key))
+        .map(|c| c.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn insert(
+    map: &mut HashMap<KeyCombination, ActionDesc>,
+    chain: &[KeyCombination],
+    desc: ActionDesc,
+) -> Result<(), &'static str> {
+    let (first, rest) = match chain.split_first() {
+        Some(split) => split,
+        None => return Err("no keys"),
+    };
+
+    if rest.is_empty() {
+        if map.contains_key(first) {
+            return Err("the keys are already bound");
+        }
+        map.insert(first.clone(), desc);
+        return Ok(());
+    }
+
+    let node = map.entry(first.clone()).or_insert_with(|| ActionDesc {
+        title: String::new(),
+        markup: None,
+        icon: None,
+        env: BTreeMap::new(),
+        cwd: None,
+        action: Action::Map(HashMap::new()),
+    });
+
+    match &mut node.action {
+        Action::Map(m) => insert(m, rest, desc),
+        _ => Err("a prefix of the keys is already bound to a command"),
+    }
+}
+
+/// Read a configuration of another tool, turning chord chains into nested
+/// maps. Entries that can't be translated are returned instead of dropped.
+pub(crate) fn import(source: Source, path: &Path) -> Result<(Config, Vec<Skipped>), Error> {
+    let text = std::fs::read_to_string(path)?;
+    let parsed = match source {
+        Source::Sxhkd => sxhkd::parse(&text),
+        Source::Xbindkeys => xbindkeys::parse(&text),
+    };
+
+    let mut map = HashMap::new();
+    let mut skipped = parsed.skipped;
+
+    for binding in parsed.bindings {
+        let desc = ActionDesc {
+            title: binding.title,
+            markup: None,
+            icon: None,
+            env: BTreeMap::new(),
+            cwd: None,
+            action: Action::Execute(Execute::Script(binding.command)),
+        };
+
+        if let Err(reason) = insert(&mut map, &binding.chain, desc) {
+            skipped.push(Skipped {
+                line: binding.line,
+                text: binding.chain.join(" "),
+                reason: reason.to_owned(),
+            });
+        }
+    }
+
+    skipped.sort_by_key(|s| s.line);
+
+    let config = Config {
+        markup: false,
+        settings: Settings::default(),
+        theme: Theme::default(),
+        include: vec![],
+        map,
+        sources: vec![],
+        overrides: vec![],
+    };
+
+    Ok((config, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::leaf;
+
+    #[test]
+    fn combinations() {
+        assert_eq!(
+            combination(&["ctrl", "alt"], "Delete").unwrap(),
+            "C-A-Delete"
+        );
+        assert_eq!(combination(&["super"], "a").unwrap(), "S-a");
+        assert!(combination(&["shift"], "a").is_err());
+        assert!(combination(&["ctrl", "shift"], "q").is_err());
+        assert!(combination(&["shift"], "Return").is_err());
+        assert!(combination(&["ctrl"], "Q").is_err());
+        assert!(combination(&["lock"], "a").is_err());
+        assert!(combination(&[], "button1").is_err());
+    }
+
+    #[test]
+    fn chains_become_maps() {
+        let mut map = HashMap::new();
+        let chain =
+            |keys: &[&str]| -> Vec<KeyCombination> { keys.iter().map(|k| k.to_string()).collect() };
+
+        insert(&mut map, &chain(&["S-a", "b"]), leaf("b")).unwrap();
+        insert(&mut map, &chain(&["S-a", "c"]), leaf("c")).unwrap();
+        match &map["S-a"].action {
+            Action::Map(m) => assert_eq!(m.len(), 2),
+            _ => panic!("S-a is not a map"),
+        }
+
+        assert!(insert(&mut map, &chain(&["S-a", "b"]), leaf("x")).is_err());
+        assert!(insert(&mut map, &chain(&["S-a", "b", "c"]), leaf("x")).is_err());
+        assert!(insert(&mut map, &[], leaf("x")).is_err());
+    }
+}
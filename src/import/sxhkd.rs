@@ -0,0 +1,308 @@
+use super::{combination, Binding, Parsed, Skipped};
+use crate::combination::KeyCombination;
+
+/// A piece of a hotkey or command: literal text, or the alternatives of a
+/// `{a,b,c}` sequence.
+enum Part {
+    Text(String),
+    Choice(Vec<String>),
+}
+
+/// Expand a range such as `1-9` or `a-z`.
+fn range(item: &str) -> Option<Vec<String>> {
+    let chars: Vec<char> = item.chars().collect();
+    match chars.as_slice() {
+        [from, '-', to]
+            if from.is_ascii_alphanumeric() && to.is_ascii_alphanumeric() && from < to =>
+        {
+            Some((*from..=*to).map(|c| c.to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+fn parts(s: &str) -> Result<Vec<Part>, String> {
+    let mut parts = vec![];
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err("unbalanced braces".to_owned()),
+        };
+        parts.push(Part::Text(rest[..start].to_owned()));
+
+        let mut choices = vec![];
+        for item in rest[start + 1..end].split(',') {
+            if let Some(range) = range(item.trim()) {
+                choices.extend(range);
+            } else if item.trim() == "_" {
+                choices.push(String::new());
+            } else {
+                choices.push(item.to_owned());
+            }
+        }
+        parts.push(Part::Choice(choices));
+        rest = &rest[end + 1..];
+    }
+    parts.push(Part::Text(rest.to_owned()));
+
+    Ok(parts)
+}
+
+/// All the alternatives described by `{...}` sequences, the first sequence
+/// varying the slowest, as sxhkd pairs hotkeys and commands.
+fn expand(s: &str) -> Result<Vec<String>, String> {
+    let mut results = vec![String::new()];
+
+    for part in parts(s)? {
+        match part {
+            Part::Text(text) => {
+                for result in results.iter_mut() {
+                    result.push_str(&text);
+                }
+            }
+            Part::Choice(choices) => {
+                results = results
+                    .iter()
+                    .flat_map(|r| choices.iter().map(move |c| format!("{}{}", r, c)))
+                    .collect();
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Translate a hotkey such as `super + a ; b` to a chain of combinations.
+fn chain(hotkey: &str) -> Result<Vec<KeyCombination>, String> {
+    if hotkey.contains(':') {
+        return Err("continuous chord chains are not supported".to_owned());
+    }
+
+    hotkey
+        .split(';')
+        .map(|chord| {
+            if chord.contains('@') {
+                return Err("key release bindings are not supported".to_owned());
+            }
+
+            // Replaying the event to the focused window has no equivalent, but is harmless
+            let chord = chord.replace('~', "");
+            let keys: Vec<&str> = chord.split('+').map(str::trim).collect();
+            match keys.split_last() {
+                Some((key, modifiers)) if !key.is_empty() => combination(modifiers, key),
+                _ => Err("empty chord".to_owned()),
+            }
+        })
+        .collect()
+}
+
+/// Join lines ending with a backslash to the next one, keeping the number of
+/// the first line.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut current: Option<(usize, String)> = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let (number, mut joined) = current.take().unwrap_or((idx + 1, String::new()));
+        if let Some(stripped) = line.strip_suffix('\\') {
+            joined.push_str(stripped);
+            current = Some((number, joined));
+        } else {
+            joined.push_str(line);
+            lines.push((number, joined));
+        }
+    }
+    lines.extend(current);
+
+    lines
+}
+
+struct Hotkey {
+    line: usize,
+    text: String,
+    /// The comment right above the hotkey
+    comment: Option<String>,
+}
+
+fn finish(parsed: &mut Parsed, hotkey: Option<Hotkey>, command: &mut Vec<String>) {
+    let hotkey = match hotkey {
+        Some(hotkey) => hotkey,
+        None => return,
+    };
+    let command = std::mem::take(command).join("\n");
+    let skip = |reason: &str| Skipped {
+        line: hotkey.line,
+        text: hotkey.text.clone(),
+        reason: reason.to_owned(),
+    };
+
+    if command.is_empty() {
+        parsed.skipped.push(skip("no command"));
+        return;
+    }
+
+    let hotkeys = match expand(&hotkey.text) {
+        Ok(hotkeys) => hotkeys,
+        Err(reason) => return parsed.skipped.push(skip(&reason)),
+    };
+
+    // Braces in the command are only sequences when the hotkey has some too
+    let commands = if hotkeys.len() > 1 {
+        match expand(&command) {
+            Ok(commands) => commands,
+            Err(reason) => return parsed.skipped.push(skip(&reason)),
+        }
+    } else {
+        vec![command]
+    };
+
+    if commands.len() != 1 && commands.len() != hotkeys.len() {
+        parsed.skipped.push(skip(
+            "the hotkey and the command have different numbers of alternatives",
+        ));
+        return;
+    }
+
+    for (idx, text) in hotkeys.iter().enumerate() {
+        let command = &commands[if commands.len() == 1 { 0 } else { idx }];
+        let title = match &hotkey.comment {
+            Some(comment) if hotkeys.len() > 1 => format!("{} ({})", comment, command),
+            Some(comment) => comment.clone(),
+            None => command.clone(),
+        };
+
+        match chain(text) {
+            Ok(chain) => parsed.bindings.push(Binding {
+                line: hotkey.line,
+                chain,
+                title,
+                command: command.clone(),
+            }),
+            Err(reason) => parsed.skipped.push(Skipped {
+                line: hotkey.line,
+                text: text.trim().to_owned(),
+                reason,
+            }),
+        }
+    }
+}
+
+/// Read a sxhkdrc: unindented hotkey lines, each followed by an indented command.
+pub(crate) fn parse(text: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut comment = None;
+    let mut hotkey = None;
+    let mut command = vec![];
+
+    for (number, line) in logical_lines(text) {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            comment = None;
+        } else if trimmed.starts_with('#') {
+            comment = Some(trimmed.trim_start_matches('#').trim().to_owned());
+        } else if line.starts_with(char::is_whitespace) {
+            if hotkey.is_some() {
+                command.push(trimmed.to_owned());
+            } else {
+                parsed.skipped.push(Skipped {
+                    line: number,
+                    text: trimmed.to_owned(),
+                    reason: "command without a hotkey".to_owned(),
+                });
+            }
+        } else {
+            finish(&mut parsed, hotkey.take(), &mut command);
+            hotkey = Some(Hotkey {
+                line: number,
+                text: trimmed.to_owned(),
+                comment: comment.take(),
+            });
+        }
+    }
+    finish(&mut parsed, hotkey, &mut command);
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_sequences_and_ranges() {
+        assert_eq!(expand("super + {a,b}").unwrap(), ["super + a", "super + b"]);
+        assert_eq!(expand("{_,shift + }x").unwrap(), ["x", "shift + x"]);
+        assert_eq!(expand("ws {1-3}").unwrap(), ["ws 1", "ws 2", "ws 3"]);
+        assert_eq!(expand("{a,b}{1,2}").unwrap(), ["a1", "a2", "b1", "b2"]);
+        assert!(expand("{a,b").is_err());
+    }
+
+    #[test]
+    fn chains() {
+        assert_eq!(chain("super + a ; b").unwrap(), ["S-a", "b"]);
+        assert_eq!(chain("~ctrl + t").unwrap(), ["C-t"]);
+        assert!(chain("ctrl + shift + t").is_err());
+        assert!(chain("super + a : b").is_err());
+        assert!(chain("@super + a").is_err());
+        assert!(chain("super + ").is_err());
+    }
+
+    #[test]
+    fn continued_lines() {
+        let lines = logical_lines("a \\\nb\nc");
+        assert_eq!(lines, [(1, "a b".to_owned()), (3, "c".to_owned())]);
+    }
+
+    #[test]
+    fn parse_file() {
+        let parsed = parse(
+            "    orphan\n# Terminal\nsuper + Return\n    alacritty\n\n\
+             super + {1,2}\n    bspc desktop -f {I,II}\n\n\
+             super + button1\n    true\n",
+        );
+
+        let bindings: Vec<_> = parsed
+            .bindings
+            .iter()
+            .map(|b| {
+                (
+                    b.line,
+                    b.chain.join(" "),
+                    b.title.as_str(),
+                    b.command.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            bindings,
+            [
+                (3, "S-Return".to_owned(), "Terminal", "alacritty"),
+                (
+                    6,
+                    "S-1".to_owned(),
+                    "bspc desktop -f I",
+                    "bspc desktop -f I"
+                ),
+                (
+                    6,
+                    "S-2".to_owned(),
+                    "bspc desktop -f II",
+                    "bspc desktop -f II"
+                ),
+            ]
+        );
+
+        let skipped: Vec<_> = parsed.skipped.iter().map(|s| s.line).collect();
+        assert_eq!(skipped, [1, 9]);
+    }
+
+    #[test]
+    fn mismatched_alternatives() {
+        let parsed = parse("super + {a,b,c}\n    run {x,y}\n");
+        assert!(parsed.bindings.is_empty());
+        assert_eq!(parsed.skipped.len(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+use super::{combination, Binding, Parsed, Skipped};
+use crate::combination::KeyCombination;
+
+/// Translate a key line such as `control+shift + q`.
+fn keys(line: &str) -> Result<KeyCombination, String> {
+    let keys: Vec<&str> = line.split('+').map(str::trim).collect();
+
+    for key in keys.iter() {
+        let lower = key.to_lowercase();
+        if lower.starts_with("m:") || lower.starts_with("c:") {
+            return Err("raw keycodes and modifier masks are not supported".to_owned());
+        } else if lower.starts_with("b:") {
+            return Err("mouse buttons are not supported".to_owned());
+        } else if lower == "release" {
+            return Err("key release bindings are not supported".to_owned());
+        }
+    }
+
+    match keys.split_last() {
+        Some((key, modifiers)) if !key.is_empty() => combination(modifiers, key),
+        _ => Err("no key".to_owned()),
+    }
+}
+
+/// Read an .xbindkeysrc: a quoted command line, followed by the line of the
+/// keys running it. Options such as `keystate_numlock` are ignored.
+pub(crate) fn parse(text: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut comment = None;
+    let mut command: Option<(usize, String)> = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let number = idx + 1;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            if command.is_none() {
+                comment = None;
+            }
+        } else if trimmed.starts_with('#') {
+            if command.is_none() {
+                comment = Some(trimmed.trim_start_matches('#').trim().to_owned());
+            }
+        } else if trimmed.starts_with('"') {
+            if let Some((line, text)) = command.take() {
+                parsed.skipped.push(Skipped {
+                    line,
+                    text,
+                    reason: "no keys".to_owned(),
+                });
+            }
+            let text = trimmed.trim_start_matches('"').trim_end_matches('"');
+            command = Some((number, text.to_owned()));
+        } else if let Some((line, text)) = command.take() {
+            let title = comment.take().unwrap_or_else(|| text.clone());
+            match keys(trimmed) {
+                Ok(combination) => parsed.bindings.push(Binding {
+                    line,
+                    chain: vec![combination],
+                    title,
+                    command: text,
+                }),
+                Err(reason) => parsed.skipped.push(Skipped {
+                    line: number,
+                    text: trimmed.to_owned(),
+                    reason,
+                }),
+            }
+        } else if !trimmed.starts_with("keystate_") {
+            parsed.skipped.push(Skipped {
+                line: number,
+                text: trimmed.to_owned(),
+                reason: "keys without a command".to_owned(),
+            });
+        }
+    }
+
+    if let Some((line, text)) = command {
+        parsed.skipped.push(Skipped {
+            line,
+            text,
+            reason: "no keys".to_owned(),
+        });
+    }
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_lines() {
+        assert_eq!(keys("control + q").unwrap(), "C-q");
+        assert!(keys("control+shift + q").is_err());
+        assert_eq!(keys("Mod4 + Return").unwrap(), "S-Return");
+        assert!(keys("m:0x4 + c:24").is_err());
+        assert!(keys("b:2").is_err());
+        assert!(keys("Release + a").is_err());
+    }
+
+    #[test]
+    fn parse_file() {
+        let parsed = parse(
+            "keystate_numlock = enable\n\
+             # Lock\n\"xscreensaver-command -lock\"\n  Mod4 + l\n\n\
+             \"firefox\"\n  Mod4 + f\n\
+             \"no keys\"\n\"xterm\"\n  b:1\n",
+        );
+
+        let bindings: Vec<_> = parsed
+            .bindings
+            .iter()
+            .map(|b| {
+                (
+                    b.line,
+                    b.chain.join(" "),
+                    b.title.as_str(),
+                    b.command.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            bindings,
+            [
+                (3, "S-l".to_owned(), "Lock", "xscreensaver-command -lock"),
+                (6, "S-f".to_owned(), "firefox", "firefox"),
+            ]
+        );
+
+        let skipped: Vec<_> = parsed
+            .skipped
+            .iter()
+            .map(|s| (s.line, s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            skipped,
+            [(8, "no keys"), (10, "mouse buttons are not supported")]
+        );
+    }
+}
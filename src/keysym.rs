@@ -51,6 +51,13 @@ pub fn is_modifier(k: KeySym) -> bool {
     return ((k >= shift_l) && (k <= hyper_r)) || iso || (k == mod_switch) || (k == xk_num_lock);
 }
 
+/// All the keysym names, sorted.
+pub fn names() -> Vec<Arc<String>> {
+    let mut names: Vec<_> = MAP.1.keys().cloned().collect();
+    names.sort();
+    names
+}
+
 pub fn sym_to_name(k: KeySym) -> Arc<String> {
     if let Some(name) = MAP.0.get(&k) {
         name.clone()
@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use cairo::ImageSurface;
+use pango::FontDescription;
+
+use crate::action::ActionDesc;
+use crate::combination::KeyCombination;
+use crate::config;
+use crate::error::Error;
+use crate::leechbar::component::icon::load_image;
+use crate::leechbar::component::text::text_size;
+
+/// Horizontal space between two columns of entries
+const COLUMN_GAP: u16 = 30;
+
+/// Horizontal space between an icon and the text beside it
+const ICON_GAP: u16 = 8;
+
+pub(crate) enum Content {
+    Text(String),
+    Markup(String),
+    Icon(ImageSurface),
+}
+
+/// A piece of content placed at a position relative to the content area.
+/// Icons are drawn as squares of the block's height.
+pub(crate) struct Block {
+    pub x: u16,
+    pub y: u16,
+    pub height: u16,
+    pub content: Content,
+}
+
+/// Positioned blocks making up the content of an OSD window.
+pub(crate) struct Layout {
+    pub width: u16,
+    pub height: u16,
+    pub blocks: Vec<Block>,
+}
+
+fn measure(text: &str, font: &FontDescription, markup: bool) -> Result<(u16, u16), Error> {
+    text_size(text, font, markup).map_err(|e| Error::Render(e.to_string()))
+}
+
+/// The text of Pango markup without its tags, or the markup itself if it
+/// doesn't parse.
+pub(crate) fn strip_markup(markup: &str) -> String {
+    match pango::parse_markup(markup, '\0') {
+        Ok((_, text, _)) => text.to_string(),
+        Err(_) => markup.to_owned(),
+    }
+}
+
+/// Escape text so that it is shown verbatim when parsed as Pango markup.
+pub(crate) fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&#39;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// The columns and rows of a page of `n` entries, given how many of them fit
+/// the monitor.
+fn grid(n: usize, rows_fit: usize, columns_fit: usize) -> (usize, usize) {
+    if n <= rows_fit {
+        (1, n)
+    } else if n <= rows_fit * columns_fit {
+        // Use just enough columns, and balance the rows among them
+        let columns = n.div_ceil(rows_fit);
+        (columns, n.div_ceil(columns))
+    } else {
+        // Reserve a line for the page footer
+        let rows = std::cmp::max(1, rows_fit.saturating_sub(1));
+        (columns_fit, rows)
+    }
+}
+
+impl Layout {
+    /// A layout of text in lines, broken at spaces where they would be wider
+    /// than `max_width`.
+    pub(crate) fn wrapped(
+        text: &str,
+        font: &FontDescription,
+        max_width: u16,
+    ) -> Result<Self, Error> {
+        let mut lines = vec![];
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let longer = if line.is_empty() {
+                    word.to_owned()
+                } else {
+                    format!("{} {}", line, word)
+                };
+
+                if !line.is_empty() && measure(&longer, font, false)?.0 > max_width {
+                    lines.push(std::mem::replace(&mut line, word.to_owned()));
+                } else {
+                    line = longer;
+                }
+            }
+            lines.push(line);
+        }
+
+        let mut width = 0;
+        let mut y = 0;
+        let mut blocks = vec![];
+        for line in lines {
+            let (line_width, height) = measure(&line, font, false)?;
+            width = width.max(line_width);
+            blocks.push(Block {
+                x: 0,
+                y,
+                height,
+                content: Content::Text(line),
+            });
+            y += height;
+        }
+
+        Ok(Self {
+            width,
+            height: y,
+            blocks,
+        })
+    }
+}
+
+/// A single line of a menu, in Pango markup, with an optional icon beside it.
+pub(crate) struct Entry {
+    pub markup: String,
+    pub icon: Option<ImageSurface>,
+}
+
+/// A list of entries shown under a header, wrapped into columns and pages
+/// so that it fits inside a given area.
+pub(crate) struct Menu {
+    header: String,
+    entries: Vec<Entry>,
+    page: usize,
+    pages: usize,
+}
+
+impl Menu {
+    pub(crate) fn new(header: &str, entries: Vec<Entry>) -> Self {
+        Self {
+            header: header.to_owned(),
+            entries,
+            page: 0,
+            pages: 1,
+        }
+    }
+
+    /// The menu listing the children of a `map` node, sorted by title.
+    /// `markup` is the default for titles that don't specify it. Icons are
+    /// decoded here once, and left out with a warning when they fail to load.
+    pub(crate) fn for_map(map: &HashMap<KeyCombination, ActionDesc>, markup: bool) -> Self {
+        let mut by_title: Vec<_> = map.iter().collect();
+        by_title.sort_by(|a, b| (&a.1.title, a.0).cmp(&(&b.1.title, b.0)));
+
+        let mut icons: HashMap<PathBuf, Option<ImageSurface>> = HashMap::new();
+        let mut icon = |path: &PathBuf| {
+            let path = config::expand_home(path);
+            icons
+                .entry(path)
+                .or_insert_with_key(|path| match load_image(path) {
+                    Ok(image) => Some(image),
+                    Err(err) => {
+                        log::warn!("{}", err);
+                        None
+                    }
+                })
+                .clone()
+        };
+
+        let entries = by_title
+            .into_iter()
+            .map(|(key, value)| {
+                let key = escape_markup(key);
+                let title = if value.markup.unwrap_or(markup) {
+                    value.title.clone()
+                } else {
+                    escape_markup(&value.title)
+                };
+
+                Entry {
+                    markup: if title.is_empty() {
+                        key
+                    } else {
+                        format!("{} - {}", key, title)
+                    },
+                    icon: value.icon.as_ref().and_then(&mut icon),
+                }
+            })
+            .collect();
+
+        Self::new("Next keys:", entries)
+    }
+
+    /// Move to the next or previous page, returning whether the page changed.
+    pub(crate) fn flip(&mut self, forward: bool) -> bool {
+        let page = if forward {
+            std::cmp::min(self.page + 1, self.pages.saturating_sub(1))
+        } else {
+            self.page.saturating_sub(1)
+        };
+
+        let changed = page != self.page;
+        self.page = page;
+        changed
+    }
+
+    /// Arrange the entries of the current page in as many columns as fit
+    /// `max_width`, paginating once even the widest arrangement overflows
+    /// `max_height`.
+    pub(crate) fn layout(
+        &mut self,
+        font: &FontDescription,
+        max_width: u16,
+        max_height: u16,
+    ) -> Result<Layout, Error> {
+        let (header_width, header_height) = measure(&self.header, font, false)?;
+        let mut text_width = 0;
+        let mut line_height = header_height;
+
+        for entry in self.entries.iter() {
+            let (w, h) = measure(&entry.markup, font, true)?;
+            text_width = std::cmp::max(text_width, w);
+            line_height = std::cmp::max(line_height, h);
+        }
+
+        // Icons are squares of the line height, followed by a gap
+        let icon_space = if self.entries.iter().any(|e| e.icon.is_some()) {
+            line_height + ICON_GAP
+        } else {
+            0
+        };
+        let column_width = icon_space + text_width;
+
+        // The header is followed by an empty line
+        let grid_y = header_height + line_height;
+        let rows_fit = std::cmp::max(1, max_height.saturating_sub(grid_y) / line_height) as usize;
+        let columns_fit =
+            std::cmp::max(1, (max_width + COLUMN_GAP) / (column_width + COLUMN_GAP)) as usize;
+
+        let n = self.entries.len();
+        let (columns, rows) = grid(n, rows_fit, columns_fit);
+
+        let per_page = std::cmp::max(1, columns * rows);
+        self.pages = std::cmp::max(1, n.div_ceil(per_page));
+        self.page = std::cmp::min(self.page, self.pages - 1);
+
+        let mut blocks = vec![Block {
+            x: 0,
+            y: 0,
+            height: header_height,
+            content: Content::Text(self.header.clone()),
+        }];
+
+        let page_entries = self
+            .entries
+            .iter()
+            .skip(self.page * per_page)
+            .take(per_page);
+        let mut used_columns = 0;
+
+        for (idx, entry) in page_entries.enumerate() {
+            let (column, row) = (idx / std::cmp::max(1, rows), idx % std::cmp::max(1, rows));
+            let x = column as u16 * (column_width + COLUMN_GAP);
+            let y = grid_y + row as u16 * line_height;
+
+            if let Some(icon) = &entry.icon {
+                blocks.push(Block {
+                    x,
+                    y,
+                    height: line_height,
+                    content: Content::Icon(icon.clone()),
+                });
+            }
+            blocks.push(Block {
+                x: x + icon_space,
+                y,
+                height: line_height,
+                content: Content::Markup(entry.markup.clone()),
+            });
+            used_columns = column as u16 + 1;
+        }
+
+        let grid_width = if used_columns > 0 {
+            used_columns * (column_width + COLUMN_GAP) - COLUMN_GAP
+        } else {
+            0
+        };
+        let mut width = std::cmp::max(header_width, grid_width);
+        let mut height = grid_y + rows as u16 * line_height;
+
+        if self.pages > 1 {
+            let footer = format!("page {}/{}", self.page + 1, self.pages);
+            let (footer_width, footer_height) = measure(&footer, font, false)?;
+            blocks.push(Block {
+                x: 0,
+                y: height,
+                height: footer_height,
+                content: Content::Text(footer),
+            });
+            width = std::cmp::max(width, footer_width);
+            height += footer_height;
+        }
+
+        Ok(Layout {
+            width,
+            height,
+            blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu(n: usize) -> Menu {
+        let entries = (0..n)
+            .map(|i| Entry {
+                markup: i.to_string(),
+                icon: None,
+            })
+            .collect();
+        Menu::new("Next keys:", entries)
+    }
+
+    #[test]
+    fn grid_single_column() {
+        assert_eq!(grid(0, 10, 3), (1, 0));
+        assert_eq!(grid(10, 10, 3), (1, 10));
+    }
+
+    #[test]
+    fn grid_balanced_columns() {
+        assert_eq!(grid(11, 10, 3), (2, 6));
+        assert_eq!(grid(21, 10, 3), (3, 7));
+        assert_eq!(grid(30, 10, 3), (3, 10));
+    }
+
+    #[test]
+    fn grid_paginated() {
+        // A line is left for the footer
+        assert_eq!(grid(31, 10, 3), (3, 9));
+        assert_eq!(grid(100, 1, 1), (1, 1));
+    }
+
+    #[test]
+    fn flip_stays_within_pages() {
+        let mut menu = menu(5);
+        menu.pages = 3;
+        assert!(!menu.flip(false));
+        assert!(menu.flip(true));
+        assert!(menu.flip(true));
+        assert!(!menu.flip(true));
+        assert_eq!(menu.page, 2);
+        assert!(menu.flip(false));
+        assert_eq!(menu.page, 1);
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(escape_markup("a<b> & 'c'"), "a&lt;b&gt; &amp; &#39;c&#39;");
+    }
+}
@@ -0,0 +1,43 @@
+use crate::leechbar::error::ErrorKind;
+use cairo::{Context, Format, ImageSurface};
+use std::path::Path;
+
+/// Decode an image file of any format supported by the `image` crate into a cairo surface.
+pub fn load_image(path: &Path) -> Result<ImageSurface, ErrorKind> {
+    let image = image::open(path)
+        .map_err(|e| format!("Unable to load image {}: {}", path.display(), e))?
+        .to_rgba();
+    let (w, h) = image.dimensions();
+
+    // Cairo expects native-endian ARGB with premultiplied alpha
+    let mut data = image.into_raw();
+    for pixel in data.chunks_mut(4) {
+        let alpha = u16::from(pixel[3]);
+        let premultiply = |c: u8| ((u16::from(c) * alpha) / 255) as u8;
+        let argb = (u32::from(pixel[3]) << 24)
+            | (u32::from(premultiply(pixel[0])) << 16)
+            | (u32::from(premultiply(pixel[1])) << 8)
+            | u32::from(premultiply(pixel[2]));
+        pixel.copy_from_slice(&argb.to_ne_bytes());
+    }
+
+    ImageSurface::create_for_data(data, Format::ARgb32, w as i32, h as i32, w as i32 * 4)
+        .map_err(|e| format!("Unable to create image surface: {:?}", e).into())
+}
+
+/// Paint an image scaled to fit a square of `size` at the given position.
+pub fn paint_image(context: &Context, image: &ImageSurface, x: f64, y: f64, size: f64) {
+    let (w, h) = (f64::from(image.get_width()), f64::from(image.get_height()));
+    if w <= 0. || h <= 0. {
+        return;
+    }
+
+    let scale = size / w.max(h);
+
+    context.save();
+    context.translate(x + (size - w * scale) / 2., y + (size - h * scale) / 2.);
+    context.scale(scale, scale);
+    context.set_source_surface(image, 0., 0.);
+    context.paint();
+    context.restore();
+}
@@ -1,2 +1,2 @@
-pub mod picture;
+pub mod icon;
 pub mod text;
@@ -1,5 +1,3 @@
-#[macro_use]
-mod macros;
 pub mod component;
 mod error;
 pub mod util;
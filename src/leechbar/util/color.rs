@@ -1,5 +1,16 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::leechbar::error::ErrorKind;
+
 /// RGBA color structure.
-#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+///
+/// In configuration files, colors are written as `#rrggbb` or `#rrggbbaa`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct Color {
     pub(crate) red: u8,
     pub(crate) green: u8,
@@ -48,3 +59,65 @@ impl From<Color> for u32 {
             + u32::from(color.blue)
     }
 }
+
+impl std::str::FromStr for Color {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, ErrorKind> {
+        let invalid = || {
+            ErrorKind::from(format!(
+                "Invalid color: {} (expected #rrggbb or #rrggbbaa)",
+                s
+            ))
+        };
+        let hex = s.strip_prefix('#').ok_or_else(invalid)?;
+        if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+            return Err(invalid());
+        }
+
+        let channel =
+            |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).map_err(|_| invalid());
+        let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+
+        Ok(Self::new(channel(0)?, channel(2)?, channel(4)?, alpha))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = ErrorKind;
+
+    fn try_from(s: String) -> Result<Self, ErrorKind> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        if color.alpha == 255 {
+            format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
+        } else {
+            format!(
+                "#{:02x}{:02x}{:02x}{:02x}",
+                color.red, color.green, color.blue, color.alpha
+            )
+        }
+    }
+}
+
+impl JsonSchema for Color {
+    fn schema_name() -> String {
+        "Color".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^#([0-9a-fA-F]{6}|[0-9a-fA-F]{8})$".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
@@ -1,78 +1,110 @@
-use std::{io::BufRead, path::{Path, PathBuf}};
+use std::{io::BufRead, path::Path};
 
 use xcb::{randr, xproto::Screen, Connection};
 use serde::Deserialize;
 use crate::error::Error;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Display {
-    #[allow(unused)] // TODO
-    name: String,
+    /// RandR output name, for example `HDMI-1`
+    #[serde(default)]
+    pub name: String,
     pub x: i32,
     pub y: i32,
     pub height: i32,
     pub width: i32,
 }
 
-pub fn xrandr_cache() -> Result<PathBuf, Error> {
-    let xdg = PathBuf::from(std::env::var("XDG_RUNTIME_DIR")?);
-    Ok(xdg.join("xrandr-cache.json"))
+/// Origin and size of a monitor
+pub(crate) type Area = ((i32, i32), (i32, i32));
+
+impl Display {
+    pub(crate) fn area(&self) -> Area {
+        ((self.x, self.y), (self.width, self.height))
+    }
 }
 
+/// Read a monitor layout written as one JSON object per line, for example:
+///
+/// ```json
+/// {"name": "HDMI-1", "x": 0, "y": 0, "width": 1920, "height": 1080}
+/// ```
 pub fn read_displays_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<Display>, Error> {
+    let path = path.as_ref();
     let file = std::fs::File::open(path)?;
     let reader = std::io::BufReader::new(file);
     let mut displays = Vec::new();
 
-    for line in reader.lines() {
+    for (idx, line) in reader.lines().enumerate() {
         let line = line?;
-        let display: Display = serde_json::from_str(&line)
-            .expect("Failed to parse the line as a Display");
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let display: Display = serde_json::from_str(&line).map_err(|e| {
+            Error::InvalidMonitorCache(path.display().to_string(), idx + 1, e)
+        })?;
         displays.push(display);
     }
 
     Ok(displays)
 }
 
+/// Ask for RandR notifications about changes of the monitor layout, returning
+/// the number of the first RandR event if the extension is present.
+pub(crate) fn select_screen_change_input(conn: &Connection, screen: &Screen)
+    -> Result<Option<u8>, Error>
+{
+    let first_event = match conn.get_extension_data(randr::id()) {
+        Some(data) if data.present() => data.first_event(),
+        _ => return Ok(None),
+    };
+
+    randr::select_input_checked(
+        conn,
+        screen.root(),
+        (randr::NOTIFY_MASK_SCREEN_CHANGE
+            | randr::NOTIFY_MASK_CRTC_CHANGE
+            | randr::NOTIFY_MASK_OUTPUT_CHANGE) as u16,
+    )
+    .request_check()?;
+
+    Ok(Some(first_event))
+}
+
+/// Query RandR for the active monitors.
 pub(crate) fn get_screens(conn: &Connection, screen: &Screen)
      -> Result<Vec<Display>, Error>
 {
     let mut screens = vec![];
-    if let Ok(displays) = read_displays_from_file(&xrandr_cache()?) {
-        return Ok(displays);
-    }
 
-    let window_dummy = conn.generate_id();
-
-    xcb::create_window(
-        &conn,
-        0,
-        window_dummy,
-        screen.root(),
-        0,
-        0,
-        1,
-        1,
-        0,
-        0,
-        0,
-        &[],
-    );
-    conn.flush();
-
-    let screen_res_cookie = randr::get_screen_resources(&conn, window_dummy);
-    let screen_res_reply = screen_res_cookie.get_reply().unwrap();
+    let screen_res_cookie = randr::get_screen_resources_current(conn, screen.root());
+    let screen_res_reply = screen_res_cookie.get_reply()?;
     let crtcs = screen_res_reply.crtcs();
 
     let mut crtc_cookies = Vec::with_capacity(crtcs.len());
     for crtc in crtcs {
-        crtc_cookies.push(randr::get_crtc_info(&conn, *crtc, 0));
+        crtc_cookies.push(randr::get_crtc_info(conn, *crtc, xcb::CURRENT_TIME));
     }
 
     for crtc_cookie in crtc_cookies.into_iter() {
         if let Ok(reply) = crtc_cookie.get_reply() {
+            // Disabled CRTCs have no size
+            if reply.width() == 0 || reply.height() == 0 {
+                continue;
+            }
+
+            let name = match reply.outputs().first() {
+                Some(output) => {
+                    let info = randr::get_output_info(conn, *output, xcb::CURRENT_TIME)
+                        .get_reply()?;
+                    String::from_utf8_lossy(info.name()).into_owned()
+                }
+                None => "".to_owned(),
+            };
+
             screens.push(Display {
-                name: "".to_owned(),
+                name,
                 x: reply.x() as i32,
                 y: reply.y() as i32,
                 height: reply.height() as i32,
@@ -81,41 +113,56 @@ pub(crate) fn get_screens(conn: &Connection, screen: &Screen)
         }
     }
 
-    xcb::destroy_window(&conn, window_dummy);
-
     Ok(screens)
 }
 
-pub(crate) fn get_largest_window(
-    conn: &Connection,
-    screen: &Screen,
-) -> Result<((i32, i32), (i32, i32)), Error> {
-    let screens = get_screens(conn, screen)?;
+pub(crate) fn get_largest_window(displays: &[Display]) -> Result<Area, Error> {
     let mut res = Err(Error::NoScreenFound);
     let mut size = 0;
 
-    for screen in screens.into_iter() {
-        let pixels = screen.width as u64 * screen.width as u64;
+    for screen in displays.iter() {
+        let pixels = screen.width as u64 * screen.height as u64;
         if pixels > size {
             size = pixels;
-            res = Ok(((screen.x, screen.y), (screen.width, screen.height)));
+            res = Ok(screen.area());
         }
     }
 
     res
 }
 
-pub(crate) fn create_gc_32(conn: &Connection, window: u32) -> Result<u32, Error> {
-    // First create a dummy pixmap with 32 bit depth
-    let pix32 = conn.generate_id();
-    xcb::create_pixmap_checked(&conn, 32, pix32, window, 1, 1).request_check()?;
+/// Find a TrueColor visual of the given depth, or the given visual id if one
+/// is specified.
+pub(crate) fn find_visualtype(screen: &Screen, depth: u8, visual_id: Option<u32>)
+    -> Option<xcb::Visualtype>
+{
+    for d in screen.allowed_depths() {
+        if d.depth() != depth {
+            continue;
+        }
 
-    // Then create a gc from that pixmap
-    let gc = conn.generate_id();
-    xcb::create_gc_checked(&conn, gc, pix32, &[]).request_check()?;
+        for visual in d.visuals() {
+            let found = match visual_id {
+                Some(id) => id == visual.visual_id(),
+                None => visual.class() == xcb::VISUAL_CLASS_TRUE_COLOR as u8,
+            };
+            if found {
+                return Some(visual);
+            }
+        }
+    }
 
-    // Free pixmap after creating the gc
-    xcb::free_pixmap_checked(&conn, pix32).request_check()?;
+    None
+}
+
+/// Whether a compositing manager is running for the screen, which makes
+/// translucent windows possible.
+pub(crate) fn compositing_manager_running(conn: &Connection, screen_num: i32)
+    -> Result<bool, Error>
+{
+    let name = format!("_NET_WM_CM_S{}", screen_num);
+    let atom = xcb::intern_atom(conn, false, &name).get_reply()?.atom();
+    let owner = xcb::get_selection_owner(conn, atom).get_reply()?.owner();
 
-    Ok(gc)
+    Ok(owner != xcb::NONE)
 }
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::action::{self, Action, ActionDesc, OnFailure, Op};
+use crate::combination::{self, Combination, KeyCombination};
+use crate::config::Config;
+use crate::keysym;
+
+/// A part of the tree which is valid, but most likely a mistake.
+pub(crate) struct Warning {
+    /// Key path of the node, or of the `map` it is about
+    pub path: Vec<KeyCombination>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path.join(" "), self.message)
+        }
+    }
+}
+
+fn lint_map(
+    map: &HashMap<KeyCombination, ActionDesc>,
+    path: &mut Vec<KeyCombination>,
+    warnings: &mut Vec<Warning>,
+) {
+    let mut keys: Vec<_> = map.keys().collect();
+    keys.sort();
+
+    let mut warn = |path: &[KeyCombination], message: String| {
+        warnings.push(Warning {
+            path: path.to_vec(),
+            message,
+        })
+    };
+
+    // Keys are looked up by the canonical spelling of what was typed
+    let mut bound: Vec<(String, &KeyCombination)> = vec![];
+    for key in keys.iter() {
+        path.push(key.to_string());
+
+        for alias in key.split(',') {
+            match combination::normalize(alias) {
+                None => warn(
+                    path,
+                    format!("`{}` has an unknown modifier, it is never matched", alias),
+                ),
+                Some(canonical) if canonical != alias => warn(
+                    path,
+                    format!("`{}` is never matched, write it `{}`", alias, canonical),
+                ),
+                Some(canonical) => {
+                    if let Ok(c) = Combination::parse(alias) {
+                        if keysym::is_modifier(c.key) {
+                            warn(
+                                path,
+                                format!("`{}` is a modifier key, pressing it is ignored", alias),
+                            );
+                        }
+                        // Keys are read without shift, as their lower case letter
+                        if is_upper_case_letter(&keysym::sym_to_name(c.key)) {
+                            warn(
+                                path,
+                                format!(
+                                    "`{}` is never matched, keys are read as lower case letters",
+                                    alias
+                                ),
+                            );
+                        }
+                    }
+                    if !bound.contains(&(canonical.clone(), key)) {
+                        bound.push((canonical, key));
+                    }
+                }
+            }
+        }
+
+        path.pop();
+    }
+
+    bound.sort();
+    for pair in bound.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            warn(
+                path,
+                format!(
+                    "`{}` is bound more than once, by `{}` and by `{}`",
+                    pair[0].0, pair[0].1, pair[1].1
+                ),
+            );
+        }
+    }
+
+    let mut titles: Vec<(&str, &KeyCombination)> = keys
+        .iter()
+        .map(|key| (map[*key].title.as_str(), *key))
+        .filter(|(title, _)| !title.is_empty())
+        .collect();
+    titles.sort();
+    for pair in titles.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            warn(
+                path,
+                format!(
+                    "`{}` and `{}` have the same title, \"{}\"",
+                    pair[0].1, pair[1].1, pair[0].0
+                ),
+            );
+        }
+    }
+
+    for key in keys.iter() {
+        path.push(key.to_string());
+        for step in map[*key].action.to_steps() {
+            let on_failure = match step.on_failure {
+                OnFailure::Run(op) => Some(op),
+                _ => None,
+            };
+            for op in std::iter::once(step.op).chain(on_failure) {
+                for name in script(&op).map(action::placeholders).unwrap_or_default() {
+                    warn(
+                        path,
+                        format!(
+                            "`{{{}}}` is not replaced in scripts, use `$KEYTREE_{}`",
+                            name,
+                            name.to_uppercase()
+                        ),
+                    );
+                }
+            }
+        }
+        path.pop();
+    }
+
+    for key in keys {
+        if let Action::Map(children) = &map[key].action {
+            path.push(key.clone());
+            if children.len() == 1 {
+                warnings.push(Warning {
+                    path: path.clone(),
+                    message: "the map has a single binding, which could be bound directly"
+                        .to_owned(),
+                });
+            }
+            lint_map(children, path, warnings);
+            path.pop();
+        }
+    }
+}
+
+fn is_upper_case_letter(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if c.is_ascii_uppercase())
+}
+
+/// The script an operation runs in a shell, if any.
+fn script(op: &Op) -> Option<&str> {
+    match op {
+        Op::Execute(execute) => execute.script(),
+        Op::Show(show) => show.execute.as_ref()?.script(),
+        Op::Reload(_) | Op::Die(_) => None,
+    }
+}
+
+/// Bindings which are shadowed, can't be reached, or are confusing, and
+/// scripts written with placeholders, in the order of the tree.
+pub(crate) fn lint(config: &Config) -> Vec<Warning> {
+    let mut warnings = vec![];
+    lint_map(&config.map, &mut vec![], &mut warnings);
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warnings(yaml: &str) -> Vec<String> {
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        lint(&config).iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn unreachable_keys() {
+        assert_eq!(
+            warnings("map: {C-Q: {execute: a}, A: {execute: b}, q: {execute: c}}"),
+            [
+                "A: `A` is never matched, keys are read as lower case letters",
+                "C-Q: `C-Q` is never matched, keys are read as lower case letters",
+            ]
+        );
+        assert_eq!(
+            warnings("map: {Control-x: {execute: a}}"),
+            ["Control-x: `Control-x` is never matched, write it `C-x`"]
+        );
+        assert!(warnings("map: {C-q: {execute: a}, F1: {execute: b}}").is_empty());
+    }
+
+    #[test]
+    fn placeholders_in_scripts() {
+        assert_eq!(
+            warnings("map:\n  a:\n    execute: echo {key}\n"),
+            ["a: `{key}` is not replaced in scripts, use `$KEYTREE_KEY`"]
+        );
+    }
+}
@@ -1,43 +1,53 @@
 #[macro_use]
 extern crate error_chain;
 
+use std::convert::TryFrom;
 use std::collections::HashMap;
-use std::fmt::Write;
 use std::os::fd::{AsRawFd, BorrowedFd};
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
-use ::config::builder::DefaultState;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 use xcb::Connection;
 
 mod action;
+mod canonical;
+mod children;
 mod cmdline;
 mod combination;
 mod config;
 mod error;
+mod export;
+mod focus;
+mod import;
 mod keysym;
+mod layout;
 mod leechbar;
+mod lint;
+mod overrides;
+mod placement;
+mod render;
+mod sequence;
+mod settings;
+mod theme;
 mod window;
 
-use crate::action::Op;
+use crate::action::{ActionDesc, Context, Execute, Op, Show};
+use crate::children::{Children, Output};
 use crate::combination::{Combination, Modifiers};
 use crate::config::Config;
 use crate::error::Error;
+use crate::focus::FocusedWindow;
 use crate::keysym::KeySym;
+use crate::layout::{strip_markup, Layout, Menu};
+use crate::sequence::Sequence;
+use crate::settings::Settings;
 use crate::window::Window;
-use crate::cmdline::Opt;
-use ::config as config_crate;
+use crate::cmdline::{Command, Opt};
 
 
 struct Main {
-    border_size: u16,
-    border_pad: u16,
     screen_num: i32,
-    format24: u32,
-    format32: u32,
-    frame: u32,
-    black: u32,
 
     meta_mod_mask: xcb::ModMask,
     alt_mod_mask: xcb::ModMask,
@@ -49,11 +59,15 @@ struct Main {
     keycode_to_keysym: Vec<KeySym>,
     keysym_to_keycode: HashMap<KeySym, u8>,
 
-    children_to_collect: Vec<std::process::Child>,
+    children: Children,
+    displays: Vec<leechbar::util::window::Display>,
+    randr_first_event: Option<u8>,
     pango_font: pango::FontDescription,
     conn: Arc<Connection>,
     opt: cmdline::Opt,
     config: Config,
+    /// The settings of the configuration, overridden by the command line
+    settings: Settings,
 }
 
 enum KeyGrabbing {
@@ -94,6 +108,8 @@ enum KeyTreeEvent {
 
     #[allow(unused)]
     UnmapNotify { event: u32 },
+
+    ScreenChange,
 }
 
 impl Main {
@@ -150,32 +166,266 @@ impl Main {
         Ok(())
     }
 
+    /// The configuration file given with `--config` or `KEYTREE_CONFIG_PATH`.
+    fn given_config(opt: &Opt) -> Option<PathBuf> {
+        opt.config
+            .clone()
+            .or_else(|| std::env::var("KEYTREE_CONFIG_PATH").ok().map(PathBuf::from))
+    }
+
+    /// Load the system-wide configuration files, with the user's one above them.
     fn load_config(opt: &Opt) -> Result<Config, Error> {
-        let config_path = if let Some(config) = &opt.config {
-            config.clone()
+        let mut layers = config::system_layers();
+
+        if let Some(path) = Main::given_config(opt) {
+            layers.push(path);
+        } else if let Some(dir) = dirs::config_dir() {
+            // The user's file is optional when there is a system-wide one
+            let path = config::find_in(&dir.join("keytree"));
+            if path.exists() || layers.is_empty() {
+                layers.push(path);
+            }
+        } else if layers.is_empty() {
+            return Err(Error::NoConfig);
+        }
+
+        let mut overrides = overrides::from_env()?;
+        overrides.extend(opt.set.iter().cloned());
+        overrides::apply(config::load(&layers)?, &overrides)
+    }
+
+    /// Origin and size of the monitor on which windows are shown.
+    pub(crate) fn monitor(&self) -> Result<leechbar::util::window::Area, Error> {
+        let setup = self.conn.get_setup();
+        let screen = setup.roots().nth(self.screen_num as usize).unwrap();
+        placement::monitor(
+            &self.conn,
+            &screen,
+            &self.displays,
+            self.settings.placement(),
+        )
+    }
+
+    /// Subscribe to changes of the monitor layout, and load the current one.
+    fn init_monitors(&mut self) -> Result<(), Error> {
+        if self.opt.monitor_cache.is_none() {
+            let setup = self.conn.get_setup();
+            let screen = setup.roots().nth(self.screen_num as usize).unwrap();
+            self.randr_first_event =
+                leechbar::util::window::select_screen_change_input(&self.conn, &screen)?;
+        }
+
+        self.refresh_monitors()
+    }
+
+    fn refresh_monitors(&mut self) -> Result<(), Error> {
+        self.displays = if let Some(path) = &self.opt.monitor_cache {
+            leechbar::util::window::read_displays_from_file(path)?
         } else {
-            if let Ok(path) = std::env::var("KEYTREE_CONFIG_PATH") {
-                PathBuf::from(path)
-            } else {
-                if let Some(dir) = dirs::config_dir() {
-                    dir.join("keytree").join("config.yaml")
+            let setup = self.conn.get_setup();
+            let screen = setup.roots().nth(self.screen_num as usize).unwrap();
+            leechbar::util::window::get_screens(&self.conn, &screen)?
+        };
+
+        for display in self.displays.iter() {
+            log::debug!("Monitor: {:?}", display);
+        }
+
+        Ok(())
+    }
+
+    /// Lay out a menu so that it fits inside the monitor along with the window frame.
+    fn menu_layout(&self, menu: &mut Menu) -> Result<Layout, Error> {
+        let (_, (width, height)) = self.monitor()?;
+        let decoration = ((self.config.theme.content_offset() + self.settings.margin()) * 2) as i32;
+        let max_width = std::cmp::max(0, width - decoration) as u16;
+        let max_height = std::cmp::max(0, height - decoration) as u16;
+        menu.layout(&self.pango_font, max_width, max_height)
+    }
+
+    /// A window showing a message, wrapped to half the width of the monitor.
+    fn message_window(&self, text: &str) -> Result<Window, Error> {
+        let ((_, _), (width, _)) = self.monitor()?;
+        let max_width = u16::try_from(width / 2).unwrap_or(u16::MAX);
+        let layout = Layout::wrapped(text, &self.pango_font, max_width)?;
+        Window::new(self, layout, "keytree")
+    }
+
+    /// Read the configuration again, showing its errors, or its warnings when
+    /// asked to. Returns whether it could be read.
+    fn reload(
+        &mut self,
+        message_win: &mut Option<Window>,
+        message_until: &mut Option<Instant>,
+    ) -> Result<bool, Error> {
+        let (text, loaded) = match Main::load_config(&self.opt) {
+            Ok(config) => {
+                self.set_config(config);
+                let warnings = lint::lint(&self.config);
+                for warning in warnings.iter() {
+                    log::warn!("{}", warning);
+                }
+                if self.settings.show_warnings() && !warnings.is_empty() {
+                    let lines: Vec<_> = warnings.iter().map(|w| w.to_string()).collect();
+                    (Some(lines.join("\n")), true)
                 } else {
-                    return Err(Error::NoConfig);
+                    (None, true)
                 }
             }
+            Err(err) => (Some(format!("{}", err)), false),
         };
 
-        let file = config_crate::File::new(
-            config_path.to_str().unwrap(),
-            config_crate::FileFormat::Yaml,
-        );
+        if let Some(text) = text {
+            self.show_error(message_win, message_until, &text)?;
+        }
+        Ok(loaded)
+    }
+
+    /// Start the steps of a list, up to the first one to wait for. Returns
+    /// false when keytree must quit.
+    fn run_steps(
+        &mut self,
+        sequence: &mut Sequence,
+        message_win: &mut Option<Window>,
+        message_until: &mut Option<Instant>,
+    ) -> Result<bool, Error> {
+        while let Some(step) = sequence.next() {
+            log::info!("Action: {:?}", step.op);
+
+            let (started, reported) = match &step.op {
+                Op::Execute(e) => {
+                    let capture = e.capture().unwrap_or_else(|| self.settings.capture());
+                    let output = if capture { Output::Log } else { Output::Discard };
+                    (self.spawn(e, sequence.context(), output), capture)
+                }
+                Op::Show(Show {
+                    execute: Some(e), ..
+                }) => (self.spawn(e, sequence.context(), Output::Keep), true),
+                Op::Show(show) => {
+                    let text = show.text.as_deref().unwrap_or_default();
+                    let text = sequence.context().substitute(text);
+                    let duration = self.show_duration(show);
+                    self.show_message(message_win, message_until, &text, duration)?;
+                    continue;
+                }
+                Op::Reload(_) => {
+                    if !self.reload(message_win, message_until)? {
+                        sequence.failed(step, 1);
+                    }
+                    continue;
+                }
+                Op::Die(_) => return Ok(false),
+            };
+
+            match started {
+                Ok(pid) => {
+                    if sequence.wait(pid, step, reported) {
+                        break;
+                    }
+                }
+                // A missing program or directory is the configuration's fault
+                Err(text) => {
+                    log::error!("{}", text);
+                    self.show_error(message_win, message_until, &text)?;
+                    sequence.failed(step, sequence::NOT_STARTED);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Let the keys dismiss a message window, wherever the focus is.
+    fn grab_keyboard(&self, message_win: &Window) -> Result<(), Error> {
+        let grab = xcb::grab_keyboard(
+            &self.conn,
+            false,
+            message_win.id(),
+            xcb::CURRENT_TIME,
+            xcb::GRAB_MODE_ASYNC as u8,
+            xcb::GRAB_MODE_ASYNC as u8,
+        )
+        .get_reply()?;
+        if grab.status() != xcb::GRAB_STATUS_SUCCESS as u8 {
+            log::warn!("Could not grab the keyboard for the message window");
+        }
+        Ok(())
+    }
+
+    /// Show a message on screen during `duration`, or until a key is pressed
+    /// if none, unless another one is shown already.
+    fn show_message(
+        &self,
+        message_win: &mut Option<Window>,
+        message_until: &mut Option<Instant>,
+        text: &str,
+        duration: Option<Duration>,
+    ) -> Result<(), Error> {
+        if message_win.is_none() {
+            *message_win = Some(self.message_window(text)?);
+            *message_until = duration.map(|duration| Instant::now() + duration);
+        }
+        Ok(())
+    }
+
+    /// The context of the commands of a leaf reached by the keys of `path`,
+    /// given the keys, the title and the window focused before keytree.
+    fn leaf_context(
+        &self,
+        context: &Context,
+        desc: &ActionDesc,
+        markup: bool,
+        path: &[String],
+        focus: xcb::Window,
+    ) -> Context {
+        let mut context = context.enter(desc);
+        context.set_var("path", &path.join(" "));
+        context.set_var("key", path.last().map(String::as_str).unwrap_or_default());
+        let title = if desc.markup.unwrap_or(markup) {
+            strip_markup(&desc.title)
+        } else {
+            desc.title.clone()
+        };
+        context.set_var("title", &title);
+
+        // The window may be gone already
+        let window = FocusedWindow::find(&self.conn, focus).unwrap_or_else(|err| {
+            log::warn!("Could not describe the focused window: {}", err);
+            None
+        });
+        focus::set_vars(&mut context, window.as_ref());
+
+        context
+    }
+
+    /// Show an error on screen, unless another message is shown already.
+    fn show_error(
+        &self,
+        message_win: &mut Option<Window>,
+        message_until: &mut Option<Instant>,
+        text: &str,
+    ) -> Result<(), Error> {
+        let duration = self.settings.error_timeout();
+        self.show_message(message_win, message_until, text, duration)
+    }
 
-        let settings = config_crate::ConfigBuilder::<DefaultState>::default()
-            .add_source(file)
-            .add_source(config_crate::Environment::with_prefix("KEYTREE_CONFIG_"))
-            .build();
-        let config = settings?.try_deserialize::<Config>()?;
-        Ok(config)
+    /// How long the result of a `show` action stays on screen.
+    fn show_duration(&self, show: &Show) -> Option<Duration> {
+        match show.duration {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => self.settings.error_timeout(),
+        }
+    }
+
+    /// Start a command in a context, returning its process id or the error
+    /// to report. It is described as written, without the context.
+    fn spawn(&mut self, e: &Execute, context: &Context, output: Output) -> Result<u32, String> {
+        let command = e.describe();
+        let process = e.resolve(context).process(self.settings.shell());
+        self.children
+            .spawn(process, command.clone(), output)
+            .map_err(|err| format!("Can't run `{}`: {}", command, err))
     }
 
     fn load_keycode_to_keysyms(&mut self) -> Result<(), Error> {
@@ -263,6 +513,8 @@ impl Main {
 
     fn looping(&mut self) -> Result<(), Error> {
         let mut key_map = self.config.map.clone();
+        let mut markup = self.config.markup;
+        let mut context = Context::default();
         let mut root_key_hit = None;
 
         if let Some(root_key) = &self.opt.root_key {
@@ -290,15 +542,18 @@ impl Main {
         }
 
         let mut win: Option<Window> = None;
-        let mut error_win: Option<Window> = None;
-        let mut error_start: Option<std::time::Instant> = None;
+        let mut menu: Option<Menu> = None;
+        let mut message_win: Option<Window> = None;
+        let mut message_until: Option<Instant> = None;
         let mut prev_focus = None;
+        let mut path: Vec<String> = vec![];
         let mut running = true;
         let mut last_focus_out: Option<Instant> = None;
+        let mut sequences: Vec<Sequence> = vec![];
 
-        while running || win.is_some() {
+        while running || win.is_some() || message_win.is_some() || !sequences.is_empty() {
             if let Some(last_focus_out) = &last_focus_out {
-                if last_focus_out.elapsed() > std::time::Duration::from_millis(30) {
+                if last_focus_out.elapsed() > self.settings.focus_timeout() {
                     if let Some(win) = &win {
                         if self.opt.root_key.is_some() {
                             log::debug!("Timeout after lost focus");
@@ -309,14 +564,48 @@ impl Main {
                 }
             }
 
-            if let Some(error_start_v) = error_start {
-                if error_start_v.elapsed() >= std::time::Duration::from_millis(1000) {
-                    if let Some(error_win) = &error_win {
-                        error_win.destroy(&self.conn)?;
+            if let Some(until) = message_until {
+                if Instant::now() >= until {
+                    if let Some(message_win) = &message_win {
+                        message_win.destroy(&self.conn)?;
+                    }
+                    message_until = None;
+                }
+            }
+
+            let mut quit = false;
+            for exited in self.children.reap() {
+                match exited.failure() {
+                    Some(text) => {
+                        log::error!("{}", text);
+                        self.show_error(&mut message_win, &mut message_until, &text)?;
+                    }
+                    None if !exited.status.success() => {
+                        log::warn!("`{}` failed, {}", exited.command, exited.status);
+                    }
+                    None => {}
+                }
+
+                if let Some(sequence) = sequences.iter_mut().find(|s| s.waits_for(exited.pid)) {
+                    if let (Some(Op::Show(show)), Some(output)) = (sequence.exited(exited.status), &exited.output) {
+                        let duration = self.show_duration(&show);
+                        self.show_message(&mut message_win, &mut message_until, output.trim_end(), duration)?;
                     }
-                    error_start = None;
+                    quit |= !self.run_steps(sequence, &mut message_win, &mut message_until)?;
                 }
             }
+            for sequence in sequences.iter_mut() {
+                if let Some(pid) = sequence.expired() {
+                    log::warn!("Stopping process {}, which ran out of time", pid);
+                    self.children.stop(pid);
+                }
+            }
+            self.children.kill_stopped();
+            sequences.retain(|sequence| !sequence.is_done());
+            if quit {
+                running = false;
+                sequences.clear();
+            }
 
             let event = if let Some(event) = root_key_hit.take() {
                 event
@@ -326,7 +615,10 @@ impl Main {
                 use nix::poll::{poll, PollFd, PollFlags};
                 let raw = self.conn.as_raw_fd();
                 let borrowed: BorrowedFd = unsafe { BorrowedFd::borrow_raw(raw) };
-                let mut fds = [PollFd::new(borrowed, PollFlags::POLLIN)];
+                let mut fds = [
+                    PollFd::new(borrowed, PollFlags::POLLIN),
+                    PollFd::new(self.children.fd(), PollFlags::POLLIN),
+                ];
                 let timeout = if last_focus_out.is_some() { 1u16 } else { 100 };
                 let _nready = poll(&mut fds, timeout).expect("poll() failed");
                 continue;
@@ -345,9 +637,18 @@ impl Main {
                                 win.id(),
                                 xcb::CURRENT_TIME,
                             ).request_check()?;
+
+                            // Keys are the menu's while it is open
+                            xcb::ungrab_keyboard(&self.conn, xcb::CURRENT_TIME);
                             self.conn.flush();
                         }
                     }
+
+                    if let (Some(message_win), None) = (&message_win, &win) {
+                        if message_win.id() == notify_win {
+                            self.grab_keyboard(message_win)?;
+                        }
+                    }
                 },
                 KeyTreeEvent::FocusOut { win_focused } => {
                     if let Some(win) = &win {
@@ -366,11 +667,9 @@ impl Main {
                 },
                 KeyTreeEvent::Expose { win: _ } => {},
                 KeyTreeEvent::ConfigureNotify { event } => {
-                    for win in [&win, &error_win] {
-                        if let Some(win) = win {
-                            if win.id() == event {
-                                win.draw(&self.conn)?;
-                            }
+                    for win in win.iter().chain(&message_win) {
+                        if win.id() == event {
+                            win.draw(&self.conn)?;
                         }
                     }
                 }
@@ -379,6 +678,13 @@ impl Main {
                         continue;
                     }
 
+                    if let (Some(message_win), None) = (&message_win, &win) {
+                        log::debug!("Dismissing message");
+                        message_win.destroy(&self.conn)?;
+                        message_until = None;
+                        continue;
+                    }
+
                     let combination = Combination {
                         key,
                         modifiers: self.x11_to_mask(state),
@@ -391,79 +697,54 @@ impl Main {
                     let mut take_focus = None;
 
                     // If it is part of a A,B, change combination_str to A,B.
-                    for item in key_map.keys() {
-                        for item2 in item.split(",") {
-                            if item2 == combination_str {
-                                combination_str = item.to_owned();
-                                break;
+                    if let Some((key, _)) = action::lookup(&key_map, &combination_str) {
+                        combination_str = key.to_owned();
+                    }
+
+                    // Unbound page keys flip between the pages of the shown menu
+                    if !key_map.contains_key(&combination_str) {
+                        if let (Some(win), Some(menu)) = (&mut win, &mut menu) {
+                            let page_key = if combination.modifiers.is_empty() {
+                                match keysym::sym_to_name(combination.key).as_str() {
+                                    "Next" => Some(true),
+                                    "Prior" => Some(false),
+                                    _ => None,
+                                }
+                            } else {
+                                None
+                            };
+
+                            if let Some(forward) = page_key {
+                                if menu.flip(forward) {
+                                    let layout = self.menu_layout(menu)?;
+                                    win.update(self, layout)?;
+                                }
+                                continue;
                             }
                         }
                     }
 
                     if let Some(desc) = key_map.get(&combination_str) {
+                        path.push(combination.to_string());
                         if let Some(m) = desc.action.action_map() {
-                            let mut display_text = String::new();
-                            writeln!(&mut display_text, "Next keys:")?;
-                            writeln!(&mut display_text, "")?;
-
-                            let v: Vec<_> = m.iter().collect();
-                            let mut by_title: Vec<_> = v
-                                .iter()
-                                .enumerate()
-                                .map(|(idx, x)| (&x.1.title, idx))
-                                .collect();
-                            by_title.sort();
-
-                            for (_, idx) in by_title.into_iter() {
-                                let (key, value) = &v[idx];
-                                if value.title.len() == 0 {
-                                    writeln!(&mut display_text, "{}", key)?;
-                                } else {
-                                    writeln!(&mut display_text, "{} - {}", key, value.title)?;
-                                }
-                            }
-                            take_focus = Some((m, display_text.trim().to_owned()));
+                            let node_markup = desc.markup.unwrap_or(markup);
+                            let menu = Menu::for_map(m, node_markup);
+                            take_focus = Some((m, menu, node_markup, context.enter(desc)));
                         } else {
-                            for op in desc.action.to_op_list() {
-                                log::info!("Action: {:?}", op);
-
-                                match op {
-                                    Op::Execute(e) => {
-                                        let mut still_existing = vec![];
-                                        for mut child in self.children_to_collect.drain(..) {
-                                            if let Ok(Some(_)) =  child.try_wait() {
-                                                // Zombie collected
-                                            } else {
-                                                still_existing.push(child);
-                                            }
-                                        }
-                                        let mut cmd = std::process::Command::new("sh");
-                                        cmd.arg("-c");
-                                        cmd.arg(e);
-                                        let child = cmd.spawn()?;
-                                        self.children_to_collect = still_existing;
-                                        self.children_to_collect.push(child);
-                                    }
-                                    Op::Reload(_) => {
-                                        match Main::load_config(&self.opt) {
-                                            Ok(config) => {
-                                                self.config = config;
-                                                break;
-                                            }
-                                            Err(err) => {
-                                                let text = format!("{}", err);
-                                                if error_win.is_none() {
-                                                    error_win = Some(Window::new(self, &text, "keytree")?);
-                                                    error_start = Some(std::time::Instant::now());
-                                                }
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    Op::Die(_) => {
-                                        running = false;
-                                    }
+                            // Without the menu, the focus is still where it was
+                            let focus = match (&win, prev_focus) {
+                                (Some(_), Some((focus, _))) => focus,
+                                _ => xcb::get_input_focus(&self.conn).get_reply()?.focus(),
+                            };
+                            let context = self.leaf_context(&context, desc, markup, &path, focus);
+                            let mut sequence = Sequence::new(desc.action.to_steps(), context);
+                            if self.run_steps(&mut sequence, &mut message_win, &mut message_until)? {
+                                if !sequence.is_done() {
+                                    sequences.push(sequence);
                                 }
+                            } else {
+                                running = false;
+                                sequences.clear();
                             }
                             revert = true;
                         }
@@ -479,18 +760,26 @@ impl Main {
                         if self.opt.root_key.is_some() {
                             running = false;
                         }
+                        menu = None;
                         key_map = self.config.map.clone();
-                    } else if let Some((take_focus, display_text)) = take_focus {
+                        markup = self.config.markup;
+                        context = Context::default();
+                        path.clear();
+                    } else if let Some((take_focus, mut new_menu, node_markup, node_context)) = take_focus {
+                        let layout = self.menu_layout(&mut new_menu)?;
                         if let Some(win) = &mut win {
-                            win.update(self, &display_text)?;
+                            win.update(self, layout)?;
                         } else {
                             let data = xcb::get_input_focus(&self.conn);
                             let r = data.get_reply()?;
                             prev_focus = Some((r.focus(), r.revert_to()));
-                            win = Some(Window::new(self, &display_text, "keytree")?);
+                            win = Some(Window::new(self, layout, "keytree")?);
                             last_focus_out = None;
                         }
+                        menu = Some(new_menu);
                         key_map = take_focus.clone();
+                        markup = node_markup;
+                        context = node_context;
                     }
                 }
                 KeyTreeEvent::DestroyNotify { event } => {
@@ -504,7 +793,15 @@ impl Main {
                         }
                     }
 
-                    for win_opt in [&mut win, &mut error_win] {
+                    if let Some(message_win) = &message_win {
+                        if message_win.id() == event {
+                            xcb::ungrab_keyboard(&self.conn, xcb::CURRENT_TIME);
+                            self.conn.flush();
+                        }
+                    }
+
+                    let menu_closed = win.as_ref().is_some_and(|win| win.id() == event);
+                    for win_opt in [&mut win, &mut message_win] {
                         if let Some(win) = win_opt {
                             if win.id() == event {
                                 *win_opt = None;
@@ -512,6 +809,15 @@ impl Main {
                             }
                         }
                     }
+
+                    // A message outliving the menu takes the keyboard over
+                    if let (true, Some(message_win)) = (menu_closed, &message_win) {
+                        self.grab_keyboard(message_win)?;
+                    }
+                }
+                KeyTreeEvent::ScreenChange => {
+                    log::info!("Monitor layout changed");
+                    self.refresh_monitors()?;
                 }
                 KeyTreeEvent::Other(_) => {}
             }
@@ -543,76 +849,42 @@ impl Main {
     }
 
     fn new(opt: &Opt, config: Config) -> Result<Self, Error> {
+        let children = Children::new()?;
         let (conn, screen_num) = xcb::Connection::connect(None).unwrap();
         let conn = Arc::new(conn);
-        let setup = conn.get_setup();
-        let screen = setup.roots().nth(screen_num as usize).unwrap();
-        let foreground = conn.generate_id();
-        let frame = conn.generate_id();
-        let black = conn.generate_id();
-        let pango_font = pango::FontDescription::from_string(&opt.font);
-
-        let (format24, format32) = leechbar::util::formats::image_formats(&conn);
-        let border_size = 1;
-        let border_pad = 10;
-
-        xcb::create_gc(
-            &conn,
-            foreground,
-            screen.root(),
-            &[
-                (xcb::GC_FOREGROUND, screen.white_pixel()),
-                (xcb::GC_GRAPHICS_EXPOSURES, 0),
-            ],
-        )
-        .request_check()?;
-
-        xcb::create_gc(
-            &conn,
-            frame,
-            screen.root(),
-            &[
-                (xcb::GC_FOREGROUND, screen.white_pixel()),
-                (xcb::GC_GRAPHICS_EXPOSURES, 0),
-            ],
-        )
-        .request_check()?;
-
-        xcb::create_gc(
-            &conn,
-            black,
-            screen.root(),
-            &[
-                (xcb::GC_FOREGROUND, screen.black_pixel()),
-                (xcb::GC_GRAPHICS_EXPOSURES, 0),
-            ],
-        )
-        .request_check()?;
+        let settings = Settings::from_options(opt).or(&config.settings);
+        let pango_font = pango::FontDescription::from_string(settings.font());
+        log::set_max_level(settings.log_level());
 
         Ok(Self {
             keycode_to_keysym: vec![],
             keysym_to_keycode: HashMap::new(),
-            children_to_collect: vec![],
+            children,
+            displays: vec![],
+            randr_first_event: None,
             meta_mod_mask: 0,
             alt_mod_mask: 0,
             super_mod_mask: 0,
             hyper_mod_mask: 0,
             num_lock_mask: 0,
             scroll_lock_mask: 0,
-            frame,
-            black,
-            format32,
-            format24,
             screen_num,
-            border_size,
-            border_pad,
             conn,
             pango_font,
             opt: opt.clone(),
             config,
+            settings,
         })
     }
 
+    /// Switch to a newly loaded configuration, applying its settings.
+    fn set_config(&mut self, config: Config) {
+        self.settings = Settings::from_options(&self.opt).or(&config.settings);
+        self.pango_font = pango::FontDescription::from_string(self.settings.font());
+        log::set_max_level(self.settings.log_level());
+        self.config = config;
+    }
+
     fn x11_to_mask(&self, mask: xcb::ModMask) -> Modifiers {
         Modifiers {
             control: mask & xcb::MOD_MASK_CONTROL != 0,
@@ -625,6 +897,14 @@ impl Main {
 
     fn classify_event(&self, event: &xcb::GenericEvent) -> KeyTreeEvent {
         let r = event.response_type() & !0x80;
+        if let Some(first_event) = self.randr_first_event {
+            if r == first_event + xcb::randr::SCREEN_CHANGE_NOTIFY
+                || r == first_event + xcb::randr::NOTIFY
+            {
+                return KeyTreeEvent::ScreenChange;
+            }
+        }
+
         match r {
             xcb::CONFIGURE_NOTIFY => {
                 let event: &xcb::ConfigureNotifyEvent = unsafe { xcb::cast_event(event) };
@@ -666,17 +946,205 @@ impl Main {
     }
 }
 
+/// Draw the OSD of the node at the end of `path` to a file.
+fn render_command(
+    opt: &Opt,
+    path: &str,
+    out: &std::path::Path,
+    width: u16,
+    height: u16,
+) -> Result<(), Error> {
+    let config = Main::load_config(opt)?;
+    let mut map = &config.map;
+    let mut markup = config.markup;
+
+    for key in path.split_whitespace() {
+        let combination = format!("{}", Combination::parse(key)?);
+        let desc = match action::lookup(map, &combination) {
+            Some((_, desc)) => desc,
+            None => return Err(Error::NoSuchNode(path.to_owned())),
+        };
+
+        markup = desc.markup.unwrap_or(markup);
+        map = match desc.action.action_map() {
+            Some(m) => m,
+            None => return Err(Error::NoSuchNode(path.to_owned())),
+        };
+    }
+
+    let settings = Settings::from_options(opt).or(&config.settings);
+    let font = pango::FontDescription::from_string(settings.font());
+    let decoration = (config.theme.content_offset() + settings.margin()) * 2;
+    let layout = Menu::for_map(map, markup).layout(
+        &font,
+        width.saturating_sub(decoration),
+        height.saturating_sub(decoration),
+    )?;
+
+    render::to_file(out, &layout, &config.theme, &font)
+}
+
+fn check_command(opt: &Opt) -> Result<(), Error> {
+    let config = Main::load_config(opt)?;
+
+    println!("Configuration files:");
+    for source in config.sources.iter() {
+        println!("  {}", source.display());
+    }
+
+    if !config.overrides.is_empty() {
+        println!("Overrides:");
+        for o in config.overrides.iter() {
+            println!("  {}", o);
+        }
+    }
+
+    println!("{} bindings", export::bindings(&config)?.len());
+
+    let warnings = lint::lint(&config);
+    if !warnings.is_empty() {
+        println!("Warnings:");
+        for warning in warnings.iter() {
+            println!("  {}", warning);
+        }
+    }
+
+    Ok(())
+}
+
+fn fmt_command(
+    opt: &Opt,
+    check: bool,
+    aliases: canonical::Aliases,
+    path: Option<&std::path::Path>,
+) -> Result<(), Error> {
+    let path = match path.map(PathBuf::from).or_else(|| Main::given_config(opt)) {
+        Some(path) => path,
+        None => match dirs::config_dir() {
+            Some(dir) => config::find_in(&dir.join("keytree")),
+            None => return Err(Error::NoConfig),
+        },
+    };
+
+    let text = std::fs::read_to_string(&path)?;
+    let formatted = canonical::format(&path, &text, aliases)?;
+    if formatted == text {
+        return Ok(());
+    }
+
+    if check {
+        return Err(Error::NotFormatted(path.display().to_string()));
+    }
+    std::fs::write(&path, formatted)?;
+    Ok(())
+}
+
+fn export_command(
+    opt: &Opt,
+    format: export::Format,
+    out: Option<&std::path::Path>,
+    font: &str,
+) -> Result<(), Error> {
+    let config = Main::load_config(opt)?;
+    let out: Box<dyn std::io::Write> = match out {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+
+    let unsupported = export::export(&config, format, out, font)?;
+    if !unsupported.is_empty() {
+        eprintln!("{} bindings could not be exported:", unsupported.len());
+        for entry in unsupported {
+            eprintln!("  {}", entry);
+        }
+    }
+
+    Ok(())
+}
+
+fn import_command(
+    source: import::Source,
+    input: &std::path::Path,
+    out: Option<&std::path::Path>,
+) -> Result<(), Error> {
+    let (config, skipped) = import::import(source, input)?;
+    let yaml = canonical::to_yaml(&config)?;
+
+    match out {
+        Some(path) => std::fs::write(path, yaml)?,
+        None => print!("{}", yaml),
+    }
+
+    if !skipped.is_empty() {
+        eprintln!("{} entries could not be imported:", skipped.len());
+        for entry in skipped {
+            eprintln!("  {}", entry);
+        }
+    }
+
+    Ok(())
+}
+
 fn main_wrap() -> Result<(), Error> {
     let opt = Opt::from_args();
+
+    // The level is set from the settings once the configuration is loaded
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Trace)
+        .init();
+    log::set_max_level(Settings::from_options(&opt).log_level());
+
     if opt.example_config {
-        print!("{}", serde_yaml::to_string(&config::example())?);
+        print!("{}", opt.format.serialize(&config::example())?);
         return Ok(());
     }
 
+    if let Some(Command::Render {
+        path,
+        out,
+        width,
+        height,
+    }) = &opt.command
+    {
+        return render_command(&opt, path, out, *width, *height);
+    }
+
+    if let Some(Command::Check) = &opt.command {
+        return check_command(&opt);
+    }
+
+    if let Some(Command::Fmt {
+        check,
+        aliases,
+        path,
+    }) = &opt.command
+    {
+        return fmt_command(&opt, *check, *aliases, path.as_deref());
+    }
+
+    if let Some(Command::Schema) = &opt.command {
+        println!("{}", serde_json::to_string_pretty(&config::schema())?);
+        return Ok(());
+    }
+
+    if let Some(Command::Export {
+        format,
+        out,
+        sheet_font,
+    }) = &opt.command
+    {
+        return export_command(&opt, *format, out.as_deref(), sheet_font);
+    }
+
+    if let Some(Command::Import { from, input, out }) = &opt.command {
+        return import_command(*from, input, out.as_deref());
+    }
+
     let mut main = Main::new(&opt, Main::load_config(&opt)?)?;
 
     main.load_keycode_to_keysyms()?;
     main.load_modifier_maps()?;
+    main.init_monitors()?;
 
     // Main loop
     main.looping()?;
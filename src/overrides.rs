@@ -0,0 +1,381 @@
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::combination;
+use crate::config::Config;
+use crate::error::Error;
+
+/// Name of the environment variable holding an override, and prefix of the
+/// others, followed by `_`
+const ENV_SET: &str = "KEYTREE_SET";
+
+/// Prefix of the environment variables setting the top-level key they name
+const ENV_KEY_PREFIX: &str = "KEYTREE_CONFIG__";
+
+/// A change to the loaded configuration, written `target=value`.
+///
+/// The target is a top-level key such as `markup`, a dotted path into a
+/// section such as `settings.font`, or a node of the tree given by its key
+/// path in brackets, optionally followed by a field: `map[Menu s].title`.
+/// The value is parsed as YAML, falling back to a plain string, and null
+/// (`~`) removes the target.
+#[derive(Debug, Clone)]
+pub(crate) struct Override {
+    pub target: String,
+    pub value: String,
+    /// Where the override was given, for reports
+    pub origin: String,
+}
+
+impl FromStr for Override {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        // Keys in brackets may contain `=`
+        let search_from = if s.starts_with("map[") {
+            s.find(']').unwrap_or(0)
+        } else {
+            0
+        };
+
+        match s[search_from..].find('=') {
+            Some(idx) => Ok(Override {
+                target: s[..search_from + idx].to_owned(),
+                value: s[search_from + idx + 1..].to_owned(),
+                origin: "--set".to_owned(),
+            }),
+            None => Err(Error::InvalidOverride(
+                s.to_owned(),
+                "expected target=value".to_owned(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Override {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={} (from {})", self.target, self.value, self.origin)
+    }
+}
+
+/// A key of the serialized configuration on the way to a target.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Field(String),
+    /// Key of a node of the tree, matched by the combinations it binds
+    Key(String),
+}
+
+impl Step {
+    fn name(&self) -> &str {
+        match self {
+            Step::Field(name) | Step::Key(name) => name,
+        }
+    }
+}
+
+/// The key of a tree section binding all the combinations of `key`, however
+/// spelled, either alone or with other aliases.
+fn bound_key(map: &Map<String, Value>, key: &str) -> Option<String> {
+    let aliases = combination::aliases(key);
+    let bound = |same: bool| {
+        map.keys().find(|k| {
+            let bound = combination::aliases(k);
+            bound == aliases || !same && bound.is_superset(&aliases)
+        })
+    };
+    bound(true).or_else(|| bound(false)).cloned()
+}
+
+/// Remove the combinations of `key` from a tree section: nodes bound only by
+/// them are removed, the others keep their other aliases.
+fn unbind(map: &mut Map<String, Value>, key: &str) {
+    let aliases = combination::aliases(key);
+    let overlapping: Vec<String> = map
+        .keys()
+        .filter(|k| !combination::aliases(k).is_disjoint(&aliases))
+        .cloned()
+        .collect();
+
+    for existing in overlapping {
+        let node = match map.remove(&existing) {
+            Some(node) => node,
+            None => continue,
+        };
+        let kept: Vec<&str> = existing
+            .split(',')
+            .filter(|alias| !combination::aliases(alias).is_subset(&aliases))
+            .collect();
+        if !kept.is_empty() {
+            map.insert(kept.join(","), node);
+        }
+    }
+}
+
+impl Override {
+    fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidOverride(format!("{}={}", self.target, self.value), reason.to_owned())
+    }
+
+    /// Keys leading to the target in the serialized configuration.
+    fn steps(&self) -> Result<Vec<Step>, Error> {
+        let mut steps = vec![];
+        let mut rest = self.target.as_str();
+
+        if let Some(tree) = rest.strip_prefix("map[") {
+            let end = tree.find(']').ok_or_else(|| self.invalid("missing ]"))?;
+            for key in tree[..end].split_whitespace() {
+                steps.push(Step::Field("map".to_owned()));
+                steps.push(Step::Key(key.to_owned()));
+            }
+            if steps.is_empty() {
+                return Err(self.invalid("no keys in brackets"));
+            }
+
+            rest = &tree[end + 1..];
+            if rest.is_empty() {
+                return Ok(steps);
+            }
+            rest = rest
+                .strip_prefix('.')
+                .ok_or_else(|| self.invalid("expected . after ]"))?;
+        }
+
+        for field in rest.split('.') {
+            if field.is_empty() {
+                return Err(self.invalid("empty field name"));
+            }
+            steps.push(Step::Field(field.to_owned()));
+        }
+
+        Ok(steps)
+    }
+
+    fn value(&self) -> Value {
+        serde_yaml::from_str(&self.value).unwrap_or_else(|_| Value::String(self.value.clone()))
+    }
+
+    fn apply(&self, root: &mut Value) -> Result<(), Error> {
+        let steps = self.steps()?;
+        let value = self.value();
+        let mut object = root;
+
+        for (idx, step) in steps.iter().enumerate() {
+            let map = object.as_object_mut().ok_or_else(|| {
+                let names: Vec<&str> = steps[..idx].iter().map(Step::name).collect();
+                self.invalid(&format!("{} is not a section", names.join(".")))
+            })?;
+
+            // Nodes are found as layers and includes find them, replaced
+            // nodes take the combinations of the key from the others
+            let last = idx + 1 == steps.len();
+            let name = match step {
+                Step::Field(name) => name.clone(),
+                Step::Key(key) if last => {
+                    unbind(map, key);
+                    key.clone()
+                }
+                Step::Key(key) => bound_key(map, key).unwrap_or_else(|| key.clone()),
+            };
+
+            if last {
+                if value.is_null() {
+                    map.remove(&name);
+                } else {
+                    map.insert(name, value);
+                }
+                break;
+            }
+
+            if !map.contains_key(&name) {
+                if value.is_null() {
+                    // Nothing to remove
+                    break;
+                }
+                map.insert(name.clone(), Value::Object(Default::default()));
+            }
+            object = map.get_mut(&name).unwrap();
+        }
+
+        Ok(())
+    }
+}
+
+/// Overrides from the environment, in the order of the names: the value of
+/// `KEYTREE_SET` and of every variable starting with `KEYTREE_SET_`, and the
+/// top-level keys set by `KEYTREE_CONFIG__<KEY>` variables.
+pub(crate) fn from_env() -> Result<Vec<Override>, Error> {
+    from_vars(std::env::vars())
+}
+
+fn from_vars(vars: impl Iterator<Item = (String, String)>) -> Result<Vec<Override>, Error> {
+    let mut vars: Vec<(String, String)> = vars.collect();
+    vars.sort();
+
+    let mut overrides = vec![];
+    for (name, value) in vars {
+        if let Some(key) = name.strip_prefix(ENV_KEY_PREFIX) {
+            overrides.push(Override {
+                target: key.to_lowercase(),
+                value,
+                origin: name,
+            });
+        } else if name == ENV_SET || name.starts_with(&format!("{}_", ENV_SET)) {
+            let mut o: Override = value.parse()?;
+            o.origin = name;
+            overrides.push(o);
+        }
+    }
+
+    Ok(overrides)
+}
+
+/// Apply overrides to a loaded configuration, in order.
+pub(crate) fn apply(config: Config, overrides: &[Override]) -> Result<Config, Error> {
+    if overrides.is_empty() {
+        return Ok(config);
+    }
+
+    let sources = config.sources.clone();
+    let mut root = serde_json::to_value(&config)?;
+    for o in overrides {
+        o.apply(&mut root)?;
+    }
+
+    let mut config: Config = serde_json::from_value(root)
+        .map_err(|e| Error::InvalidOverride("overrides".to_owned(), e.to_string()))?;
+    config.sources = sources;
+    config.overrides = overrides.to_vec();
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(s: &str) -> Override {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_target_and_value() {
+        let o = parse("settings.font=Monospace 12");
+        assert_eq!(
+            (o.target.as_str(), o.value.as_str()),
+            ("settings.font", "Monospace 12")
+        );
+
+        let o = parse("map[Menu C-=].title=a=b");
+        assert_eq!(
+            (o.target.as_str(), o.value.as_str()),
+            ("map[Menu C-=].title", "a=b")
+        );
+
+        assert!("markup".parse::<Override>().is_err());
+    }
+
+    fn names(target: &str) -> Vec<String> {
+        let steps = parse(target).steps().unwrap();
+        steps
+            .iter()
+            .map(|step| match step {
+                Step::Field(name) => name.clone(),
+                Step::Key(key) => format!("[{}]", key),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(names("markup=true"), ["markup"]);
+        assert_eq!(
+            names("map[Menu s].title=x"),
+            ["map", "[Menu]", "map", "[s]", "title"]
+        );
+        assert_eq!(names("map[Menu]=~"), ["map", "[Menu]"]);
+
+        for target in &[
+            "map[]=x",
+            "map[Menu=x",
+            "map[Menu]title=x",
+            "settings..font=x",
+        ] {
+            assert!(parse(target).steps().is_err(), "{}", target);
+        }
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(parse("a=true").value(), json!(true));
+        assert_eq!(parse("a=3").value(), json!(3));
+        assert_eq!(parse("a={execute: ls}").value(), json!({"execute": "ls"}));
+        assert_eq!(parse("a=~").value(), Value::Null);
+        assert_eq!(parse("a=[unclosed").value(), json!("[unclosed"));
+    }
+
+    #[test]
+    fn apply_sets_and_removes() {
+        let mut root = json!({"settings": {"font": "Sans"}, "map": {"Menu": {"map": {}}}});
+
+        parse("settings.font=Mono").apply(&mut root).unwrap();
+        parse("map[Menu t].execute=xterm").apply(&mut root).unwrap();
+        parse("map[Other x]=~").apply(&mut root).unwrap();
+        assert_eq!(root["settings"]["font"], json!("Mono"));
+        assert_eq!(root["map"]["Menu"]["map"]["t"], json!({"execute": "xterm"}));
+        assert!(root["map"].get("Other").is_none());
+
+        parse("map[Menu t]=~").apply(&mut root).unwrap();
+        assert_eq!(root["map"]["Menu"]["map"], json!({}));
+
+        assert!(parse("settings.font.size=3").apply(&mut root).is_err());
+    }
+
+    #[test]
+    fn environment() {
+        let vars = vec![
+            ("KEYTREE_SET_B", "settings.font=Mono"),
+            ("KEYTREE_SETTINGS_DIR", "/etc"),
+            ("KEYTREE_SET", "markup=true"),
+            ("KEYTREE_CONFIG__MARKUP", "false"),
+            ("KEYTREE_CONFIG_PATH", "/etc/keytree.yaml"),
+        ];
+        let overrides = from_vars(
+            vars.into_iter()
+                .map(|(name, value)| (name.to_owned(), value.to_owned())),
+        )
+        .unwrap();
+
+        let found: Vec<String> = overrides.iter().map(|o| o.to_string()).collect();
+        assert_eq!(
+            found,
+            [
+                "markup=false (from KEYTREE_CONFIG__MARKUP)",
+                "markup=true (from KEYTREE_SET)",
+                "settings.font=Mono (from KEYTREE_SET_B)",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_matches_combinations() {
+        let mut root = json!({"map": {"Control-x": {"map": {"a,b": {"execute": "ab"}}}}});
+
+        parse("map[C-x a].title=A or B").apply(&mut root).unwrap();
+        let tree = &root["map"]["Control-x"]["map"];
+        assert_eq!(tree["a,b"], json!({"execute": "ab", "title": "A or B"}));
+
+        parse("map[C-x a]={execute: a}").apply(&mut root).unwrap();
+        let tree = &root["map"]["Control-x"]["map"];
+        assert_eq!(tree["a"], json!({"execute": "a"}));
+        assert_eq!(tree["b"], json!({"execute": "ab", "title": "A or B"}));
+
+        parse("map[C-x b]=~").apply(&mut root).unwrap();
+        assert_eq!(
+            root["map"]["Control-x"]["map"],
+            json!({"a": {"execute": "a"}})
+        );
+    }
+}
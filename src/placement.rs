@@ -0,0 +1,309 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use xcb::{randr, xproto::Screen, Connection};
+
+use crate::cmdline;
+use crate::error::Error;
+use crate::leechbar::util::window::{get_largest_window, Area, Display};
+
+/// Policy for choosing the monitor on which windows are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum Placement {
+    /// The monitor with the most pixels
+    Largest,
+    /// The RandR primary output
+    Primary,
+    /// The monitor under the mouse pointer
+    Pointer,
+    /// The monitor showing the center of the focused window
+    Focused,
+    /// The whole X screen, regardless of monitors
+    Fixed,
+}
+
+impl Placement {
+    const ALL: [Placement; 5] = [
+        Placement::Largest,
+        Placement::Primary,
+        Placement::Pointer,
+        Placement::Focused,
+        Placement::Fixed,
+    ];
+}
+
+impl FromStr for Placement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "largest" => Placement::Largest,
+            "primary" => Placement::Primary,
+            "pointer" => Placement::Pointer,
+            "focused" => Placement::Focused,
+            "fixed" => Placement::Fixed,
+            _ => return Err(Error::InvalidPlacement(s.to_owned())),
+        })
+    }
+}
+
+impl TryFrom<String> for Placement {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Error> {
+        s.parse()
+    }
+}
+
+impl From<Placement> for String {
+    fn from(placement: Placement) -> String {
+        match placement {
+            Placement::Largest => "largest",
+            Placement::Primary => "primary",
+            Placement::Pointer => "pointer",
+            Placement::Focused => "focused",
+            Placement::Fixed => "fixed",
+        }
+        .to_owned()
+    }
+}
+
+/// Schema of a type written as one of a few strings.
+pub(crate) fn string_enum_schema(values: Vec<String>) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(values.into_iter().map(Into::into).collect()),
+        ..Default::default()
+    }
+    .into()
+}
+
+impl JsonSchema for Placement {
+    fn schema_name() -> String {
+        "Placement".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        string_enum_schema(Placement::ALL.iter().map(|p| String::from(*p)).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Start,
+    Center,
+    End,
+}
+
+/// Point of the monitor to which windows are attached.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Anchor {
+    horizontal: Align,
+    vertical: Align,
+}
+
+impl FromStr for Anchor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        use Align::*;
+
+        let (vertical, horizontal) = match s {
+            "top-left" => (Start, Start),
+            "top" => (Start, Center),
+            "top-right" => (Start, End),
+            "left" => (Center, Start),
+            "center" | "centre" => (Center, Center),
+            "right" => (Center, End),
+            "bottom-left" => (End, Start),
+            "bottom" => (End, Center),
+            "bottom-right" => (End, End),
+            _ => return Err(Error::InvalidAnchor(s.to_owned())),
+        };
+
+        Ok(Anchor {
+            horizontal,
+            vertical,
+        })
+    }
+}
+
+impl TryFrom<String> for Anchor {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Error> {
+        s.parse()
+    }
+}
+
+impl From<Anchor> for String {
+    fn from(anchor: Anchor) -> String {
+        use Align::*;
+
+        match (anchor.vertical, anchor.horizontal) {
+            (Start, Start) => "top-left",
+            (Start, Center) => "top",
+            (Start, End) => "top-right",
+            (Center, Start) => "left",
+            (Center, Center) => "center",
+            (Center, End) => "right",
+            (End, Start) => "bottom-left",
+            (End, Center) => "bottom",
+            (End, End) => "bottom-right",
+        }
+        .to_owned()
+    }
+}
+
+impl JsonSchema for Anchor {
+    fn schema_name() -> String {
+        "Anchor".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        use Align::*;
+
+        let mut anchors = vec![];
+        for &vertical in &[Start, Center, End] {
+            for &horizontal in &[Start, Center, End] {
+                anchors.push(String::from(Anchor {
+                    horizontal,
+                    vertical,
+                }));
+            }
+        }
+
+        string_enum_schema(anchors)
+    }
+}
+
+fn contains(area: &Area, x: i32, y: i32) -> bool {
+    let ((ax, ay), (aw, ah)) = *area;
+    x >= ax && x < ax + aw && y >= ay && y < ay + ah
+}
+
+/// The monitor containing the given point, or the largest one if none does.
+fn monitor_at(displays: &[Display], x: i32, y: i32) -> Result<Area, Error> {
+    for display in displays.iter() {
+        if contains(&display.area(), x, y) {
+            return Ok(display.area());
+        }
+    }
+
+    get_largest_window(displays)
+}
+
+fn primary_monitor(
+    conn: &Connection,
+    screen: &Screen,
+    displays: &[Display],
+) -> Result<Option<Area>, Error> {
+    let output = randr::get_output_primary(conn, screen.root())
+        .get_reply()?
+        .output();
+    if output == xcb::NONE {
+        return Ok(None);
+    }
+
+    let info = randr::get_output_info(conn, output, xcb::CURRENT_TIME).get_reply()?;
+    let name = String::from_utf8_lossy(info.name());
+
+    Ok(displays
+        .iter()
+        .find(|display| display.name == name)
+        .map(|display| display.area()))
+}
+
+fn focused_window_center(conn: &Connection, screen: &Screen) -> Result<Option<(i32, i32)>, Error> {
+    let focus = xcb::get_input_focus(conn).get_reply()?.focus();
+    if focus == xcb::NONE || focus == xcb::INPUT_FOCUS_POINTER_ROOT || focus == screen.root() {
+        return Ok(None);
+    }
+
+    let geometry = xcb::get_geometry(conn, focus).get_reply()?;
+    let origin = xcb::translate_coordinates(conn, focus, screen.root(), 0, 0).get_reply()?;
+
+    Ok(Some((
+        origin.dst_x() as i32 + geometry.width() as i32 / 2,
+        origin.dst_y() as i32 + geometry.height() as i32 / 2,
+    )))
+}
+
+/// Choose the area in which windows are placed, according to the policy.
+pub(crate) fn monitor(
+    conn: &Connection,
+    screen: &Screen,
+    displays: &[Display],
+    placement: Placement,
+) -> Result<Area, Error> {
+    let pointer = || -> Result<Area, Error> {
+        let r = xcb::query_pointer(conn, screen.root()).get_reply()?;
+        monitor_at(displays, r.root_x() as i32, r.root_y() as i32)
+    };
+
+    match placement {
+        Placement::Largest => get_largest_window(displays),
+        Placement::Primary => match primary_monitor(conn, screen, displays)? {
+            Some(area) => Ok(area),
+            None => get_largest_window(displays),
+        },
+        Placement::Pointer => pointer(),
+        Placement::Focused => match focused_window_center(conn, screen)? {
+            Some((x, y)) => monitor_at(displays, x, y),
+            None => pointer(),
+        },
+        Placement::Fixed => Ok((
+            (0, 0),
+            (
+                screen.width_in_pixels() as i32,
+                screen.height_in_pixels() as i32,
+            ),
+        )),
+    }
+}
+
+fn align(align: Align, start: i32, measure: i32, size: i32, margin: i32) -> i32 {
+    let offset = match align {
+        Align::Start => margin,
+        Align::Center => (measure - size) / 2,
+        Align::End => measure - size - margin,
+    };
+
+    start + std::cmp::max(0, offset)
+}
+
+/// Position of a window of the given size inside the area. The window is
+/// attached to the anchor if there is one, otherwise it is centered on
+/// `position`.
+pub(crate) fn position(
+    area: &Area,
+    position: &str,
+    anchor: Option<Anchor>,
+    margin: u16,
+    width: u16,
+    height: u16,
+) -> Result<(i16, i16), Error> {
+    let ((ax, ay), (aw, ah)) = *area;
+
+    if let Some(anchor) = anchor {
+        let margin = margin as i32;
+        let x = align(anchor.horizontal, ax, aw, width as i32, margin);
+        let y = align(anchor.vertical, ay, ah, height as i32, margin);
+        return Ok((x as i16, y as i16));
+    }
+
+    if let Some((pos_x, pos_y)) = position.split_once(",") {
+        let x = cmdline::parse_position(pos_x, width, aw as u16)?;
+        let y = cmdline::parse_position(pos_y, height, ah as u16)?;
+        Ok((ax as i16 + x, ay as i16 + y))
+    } else {
+        Err(Error::InvalidPosition)
+    }
+}
@@ -0,0 +1,175 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use cairo::{Context, Format, ImageSurface, LinearGradient, Operator, SvgSurface};
+use pango::FontDescription;
+
+use crate::error::Error;
+use crate::layout::{Content, Layout};
+use crate::leechbar::component::icon;
+use crate::leechbar::component::text;
+use crate::leechbar::util::Color;
+use crate::theme::Theme;
+
+/// Size of a window showing the layout. The shadow is only drawn when
+/// `translucent`, and takes extra space to the right and below.
+pub(crate) fn window_size(layout: &Layout, theme: &Theme, translucent: bool) -> (u16, u16) {
+    let decoration = theme.content_offset() * 2;
+    let shadow = if translucent { theme.shadow } else { 0 };
+
+    (
+        layout.width + decoration + shadow,
+        layout.height + decoration + shadow,
+    )
+}
+
+fn rounded_rectangle(context: &Context, x: f64, y: f64, w: f64, h: f64, radius: f64) {
+    let r = radius.min(w / 2.).min(h / 2.);
+    if r <= 0. {
+        context.rectangle(x, y, w, h);
+        return;
+    }
+
+    context.new_sub_path();
+    context.arc(x + w - r, y + r, r, -PI / 2., 0.);
+    context.arc(x + w - r, y + h - r, r, 0., PI / 2.);
+    context.arc(x + r, y + h - r, r, PI / 2., PI);
+    context.arc(x + r, y + r, r, PI, 3. * PI / 2.);
+    context.close_path();
+}
+
+fn set_color(context: &Context, color: Color, opacity: f64) {
+    let (r, g, b, a) = color.as_fractions();
+    context.set_source_rgba(r, g, b, a * opacity);
+}
+
+/// Draw the window frame and the layout content.
+///
+/// When not `translucent`, opacity is ignored and corners are square, so that
+/// nothing depends on an alpha channel being composited.
+pub(crate) fn draw(
+    context: &Context,
+    layout: &Layout,
+    theme: &Theme,
+    font: &FontDescription,
+    translucent: bool,
+) {
+    let (width, height) = window_size(layout, theme, translucent);
+    let (opacity, radius, shadow) = if translucent {
+        (
+            theme.opacity.clamp(0., 1.),
+            f64::from(theme.corner_radius),
+            f64::from(theme.shadow),
+        )
+    } else {
+        (1., 0., 0.)
+    };
+    let frame_width = f64::from(width) - shadow;
+    let frame_height = f64::from(height) - shadow;
+
+    // Start from a fully transparent window
+    context.save();
+    context.set_operator(Operator::Source);
+    context.set_source_rgba(0., 0., 0., 0.);
+    context.paint();
+    context.restore();
+
+    // Stacked offset copies of the frame, darker where they overlap
+    let shadow_layers = shadow as u16;
+    for i in 1..=shadow_layers {
+        let offset = f64::from(i);
+        rounded_rectangle(context, offset, offset, frame_width, frame_height, radius);
+        context.set_source_rgba(0., 0., 0., 0.5 * opacity / shadow);
+        context.fill();
+    }
+
+    let border_width = f64::from(theme.border_width);
+    let inset = border_width / 2.;
+    rounded_rectangle(
+        context,
+        inset,
+        inset,
+        frame_width - border_width,
+        frame_height - border_width,
+        radius,
+    );
+
+    if let Some(gradient) = theme.gradient {
+        let pattern = LinearGradient::new(0., 0., 0., frame_height);
+        for (offset, color) in [(0., theme.background), (1., gradient)].iter() {
+            let (r, g, b, a) = color.as_fractions();
+            pattern.add_color_stop_rgba(*offset, r, g, b, a * opacity);
+        }
+        context.set_source(&pattern);
+    } else {
+        set_color(context, theme.background, opacity);
+    }
+    context.fill_preserve();
+
+    if theme.border_width > 0 {
+        context.set_line_width(border_width);
+        set_color(context, theme.border, opacity);
+        context.stroke();
+    } else {
+        context.new_path();
+    }
+
+    let origin = f64::from(theme.content_offset());
+    for block in layout.blocks.iter() {
+        let x = origin + f64::from(block.x);
+        let y = origin + f64::from(block.y);
+
+        let (content, markup) = match &block.content {
+            Content::Text(text) => (text, false),
+            Content::Markup(text) => (text, true),
+            Content::Icon(image) => {
+                icon::paint_image(context, image, x, y, f64::from(block.height));
+                continue;
+            }
+        };
+
+        let pango_layout = text::layout(context, content, font, markup);
+        set_color(context, theme.foreground, 1.);
+
+        // Center text vertically inside the block
+        let (_, text_height) = pango_layout.get_pixel_size();
+        context.move_to(
+            x,
+            y + (f64::from(block.height) - f64::from(text_height)) / 2.,
+        );
+        pangocairo::functions::show_layout(context, &pango_layout);
+    }
+}
+
+/// Draw a translucent OSD to an image file, with the format chosen by the
+/// extension of `path`: `.png` or `.svg`.
+pub(crate) fn to_file(
+    path: &Path,
+    layout: &Layout,
+    theme: &Theme,
+    font: &FontDescription,
+) -> Result<(), Error> {
+    let (width, height) = window_size(layout, theme, true);
+    let cairo_error = |e| Error::Render(format!("{:?}", e));
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("png") => {
+            let surface = ImageSurface::create(Format::ARgb32, width as i32, height as i32)
+                .map_err(cairo_error)?;
+            draw(&Context::new(&surface), layout, theme, font, true);
+            let mut file = std::fs::File::create(path)?;
+            surface
+                .write_to_png(&mut file)
+                .map_err(|e| Error::Render(e.to_string()))?;
+        }
+        Some("svg") => {
+            let surface = SvgSurface::new(f64::from(width), f64::from(height), Some(path))
+                .map_err(cairo_error)?;
+            draw(&Context::new(&surface), layout, theme, font, true);
+            surface.finish();
+        }
+        _ => return Err(Error::UnknownOutputFormat(path.display().to_string())),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+//! Lists run step by step. Steps are started from the event loop, the next one
+//! once the process of the previous one has exited, so that keys and windows
+//! are still handled while a list runs.
+
+use std::collections::VecDeque;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+
+use crate::action::{Context, OnFailure, Op, Step};
+
+/// Variable holding the exit status of the previous step
+const STATUS_VARIABLE: &str = "KEYTREE_STATUS";
+
+/// Exit status of a step whose command could not be started, as in the shell
+pub(crate) const NOT_STARTED: i32 = 127;
+
+/// The step being waited for.
+struct Waiting {
+    pid: u32,
+    /// When the step is stopped, unless it was already
+    deadline: Option<Instant>,
+    step: Step,
+}
+
+pub(crate) struct Sequence {
+    steps: VecDeque<Step>,
+    context: Context,
+    waiting: Option<Waiting>,
+}
+
+impl Sequence {
+    pub(crate) fn new(steps: Vec<Step>, context: Context) -> Self {
+        Self {
+            steps: steps.into(),
+            context,
+            waiting: None,
+        }
+    }
+
+    /// The next step to start, as the configuration writes it.
+    pub(crate) fn next(&mut self) -> Option<Step> {
+        self.steps.pop_front()
+    }
+
+    /// The context the steps are run in. Their commands see the exit status
+    /// of the previous step as `KEYTREE_STATUS`.
+    pub(crate) fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Wait for the process of a step before starting the next one, unless
+    /// nothing depends on how it ends: it is then left to run detached.
+    /// `reported` tells whether its failure is reported. Returns whether it
+    /// is waited for.
+    pub(crate) fn wait(&mut self, pid: u32, step: Step, reported: bool) -> bool {
+        let needed = reported
+            || step.timeout.is_some()
+            || matches!(step.op, Op::Show(_))
+            || matches!(step.on_failure, OnFailure::Run(_))
+            || !self.steps.is_empty();
+        if needed {
+            self.waiting = Some(Waiting {
+                pid,
+                deadline: step
+                    .timeout
+                    .map(|ms| Instant::now() + Duration::from_millis(ms)),
+                step,
+            });
+        }
+        needed
+    }
+
+    pub(crate) fn waits_for(&self, pid: u32) -> bool {
+        self.waiting
+            .as_ref()
+            .is_some_and(|waiting| waiting.pid == pid)
+    }
+
+    fn set_status(&mut self, code: i32) {
+        self.context
+            .env
+            .insert(STATUS_VARIABLE.to_owned(), code.to_string());
+    }
+
+    /// The process waited for has exited. Returns the operation it ran.
+    pub(crate) fn exited(&mut self, status: ExitStatus) -> Option<Op> {
+        let waiting = self.waiting.take()?;
+
+        // Killed by a signal, as the shell reports it
+        let code = status
+            .code()
+            .or_else(|| status.signal().map(|signal| 128 + signal));
+        self.set_status(code.unwrap_or(1));
+        if !status.success() {
+            self.fail(waiting.step.on_failure);
+        }
+
+        Some(waiting.step.op)
+    }
+
+    /// A step failed without a process to wait for.
+    pub(crate) fn failed(&mut self, step: Step, code: i32) {
+        self.set_status(code);
+        self.fail(step.on_failure);
+    }
+
+    fn fail(&mut self, on_failure: OnFailure) {
+        match on_failure {
+            OnFailure::Stop => self.steps.clear(),
+            OnFailure::Continue => {}
+            OnFailure::Run(op) => {
+                self.steps.clear();
+                self.steps.push_back(op.into());
+            }
+        }
+    }
+
+    /// The process of the step waited for, the first time it is found to
+    /// have run out of time.
+    pub(crate) fn expired(&mut self) -> Option<u32> {
+        let waiting = self.waiting.as_mut()?;
+        if waiting.deadline? > Instant::now() {
+            return None;
+        }
+
+        waiting.deadline = None;
+        Some(waiting.pid)
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.waiting.is_none() && self.steps.is_empty()
+    }
+}
@@ -0,0 +1,200 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::LevelFilter;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::cmdline::Opt;
+use crate::error::Error;
+use crate::placement::{Anchor, Placement};
+
+const DEFAULT_FONT: &str = "normal 25";
+const DEFAULT_POSITION: &str = "%50,%50";
+const DEFAULT_ERROR_TIMEOUT: u64 = 10000;
+const DEFAULT_FOCUS_TIMEOUT: u64 = 30;
+const DEFAULT_SHELL: &str = "sh";
+
+/// Most verbose messages to log, written in any case.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct LogLevel(pub LevelFilter);
+
+impl FromStr for LogLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        s.parse()
+            .map(LogLevel)
+            .map_err(|_| Error::InvalidLogLevel(s.to_owned()))
+    }
+}
+
+impl TryFrom<String> for LogLevel {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Error> {
+        s.parse()
+    }
+}
+
+impl From<LogLevel> for String {
+    fn from(level: LogLevel) -> String {
+        level.0.to_string().to_lowercase()
+    }
+}
+
+impl JsonSchema for LogLevel {
+    fn schema_name() -> String {
+        "LogLevel".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let levels = [
+            LevelFilter::Off,
+            LevelFilter::Error,
+            LevelFilter::Warn,
+            LevelFilter::Info,
+            LevelFilter::Debug,
+            LevelFilter::Trace,
+        ];
+
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(
+                levels
+                    .iter()
+                    .map(|level| String::from(LogLevel(*level)).into())
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Options that can be given both in the `settings` section of the
+/// configuration file and on the command line, which takes precedence.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, JsonSchema)]
+#[serde(default)]
+pub struct Settings {
+    /// Pango font string, for example "normal 100" for big text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// Center of the windows, as `x,y` in pixels or percents of the monitor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placement: Option<Placement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Anchor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin: Option<u16>,
+    /// Milliseconds during which errors are shown, unless a key is pressed,
+    /// or 0 to wait for a key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_timeout: Option<u64>,
+    /// Milliseconds after the window loses focus before giving up, with `--root-key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<LogLevel>,
+    /// Program running `execute` actions with `-c`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Show the warnings of `keytree check` on screen after a reload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_warnings: Option<bool>,
+    /// Write the standard output and error of commands to a log file, and
+    /// show the commands that fail on screen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture: Option<bool>,
+}
+
+impl Settings {
+    /// The settings given on the command line.
+    pub(crate) fn from_options(opt: &Opt) -> Self {
+        Self {
+            font: opt.font.clone(),
+            position: opt.position.clone(),
+            placement: opt.placement,
+            anchor: opt.anchor,
+            margin: opt.margin,
+            error_timeout: None,
+            focus_timeout: None,
+            log_level: opt.log_level,
+            shell: opt.shell.clone(),
+            show_warnings: None,
+            capture: None,
+        }
+    }
+
+    /// These settings, with the unset ones taken from `below`.
+    pub(crate) fn or(self, below: &Settings) -> Self {
+        Self {
+            font: self.font.or_else(|| below.font.clone()),
+            position: self.position.or_else(|| below.position.clone()),
+            placement: self.placement.or(below.placement),
+            anchor: self.anchor.or(below.anchor),
+            margin: self.margin.or(below.margin),
+            error_timeout: self.error_timeout.or(below.error_timeout),
+            focus_timeout: self.focus_timeout.or(below.focus_timeout),
+            log_level: self.log_level.or(below.log_level),
+            shell: self.shell.or_else(|| below.shell.clone()),
+            show_warnings: self.show_warnings.or(below.show_warnings),
+            capture: self.capture.or(below.capture),
+        }
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn font(&self) -> &str {
+        self.font.as_deref().unwrap_or(DEFAULT_FONT)
+    }
+
+    pub(crate) fn position(&self) -> &str {
+        self.position.as_deref().unwrap_or(DEFAULT_POSITION)
+    }
+
+    pub(crate) fn placement(&self) -> Placement {
+        self.placement.unwrap_or(Placement::Largest)
+    }
+
+    pub(crate) fn margin(&self) -> u16 {
+        self.margin.unwrap_or(0)
+    }
+
+    pub(crate) fn error_timeout(&self) -> Option<Duration> {
+        match self.error_timeout.unwrap_or(DEFAULT_ERROR_TIMEOUT) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    pub(crate) fn focus_timeout(&self) -> Duration {
+        Duration::from_millis(self.focus_timeout.unwrap_or(DEFAULT_FOCUS_TIMEOUT))
+    }
+
+    pub(crate) fn log_level(&self) -> LevelFilter {
+        self.log_level
+            .map(|level| level.0)
+            .unwrap_or(LevelFilter::Warn)
+    }
+
+    pub(crate) fn shell(&self) -> &str {
+        self.shell.as_deref().unwrap_or(DEFAULT_SHELL)
+    }
+
+    pub(crate) fn show_warnings(&self) -> bool {
+        self.show_warnings.unwrap_or(false)
+    }
+
+    pub(crate) fn capture(&self) -> bool {
+        self.capture.unwrap_or(false)
+    }
+}
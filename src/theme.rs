@@ -0,0 +1,53 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::leechbar::util::Color;
+
+/// Appearance of the OSD windows.
+///
+/// Translucency, rounded corners and the shadow need a compositing manager.
+/// Without one, windows are drawn opaque and with square corners.
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct Theme {
+    /// Color of the text
+    pub foreground: Color,
+    /// Color of the window background
+    pub background: Color,
+    /// When set, the background is a vertical gradient ending in this color
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient: Option<Color>,
+    /// Color of the frame around the window
+    pub border: Color,
+    pub border_width: u16,
+    /// Space between the frame and the text
+    pub padding: u16,
+    /// Opacity of the background and the frame, from 0 to 1
+    pub opacity: f64,
+    pub corner_radius: u16,
+    /// Size of the drop shadow below and to the right of the window
+    pub shadow: u16,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            foreground: Color::new(255, 255, 255, 255),
+            background: Color::new(0, 0, 0, 255),
+            gradient: None,
+            border: Color::new(255, 255, 255, 255),
+            border_width: 1,
+            padding: 10,
+            opacity: 1.0,
+            corner_radius: 0,
+            shadow: 0,
+        }
+    }
+}
+
+impl Theme {
+    /// Offset of the content from the window edges
+    pub(crate) fn content_offset(&self) -> u16 {
+        self.border_width + self.padding
+    }
+}
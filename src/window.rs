@@ -1,19 +1,20 @@
 use xcb::Connection;
 
 use crate::error::Error;
-use crate::leechbar::component::text::Text;
-use crate::{cmdline, leechbar};
+use crate::layout::Layout;
+use crate::theme::Theme;
+use crate::{leechbar, placement, render};
 
 pub(crate) struct Window {
     id: u32,
-    frame: u32,
-    black: u32,
-    window_pict: u32,
-    border_size: u16,
-    border_pad: u16,
-    text_width: u16,
-    text_height: u16,
-    text: Text,
+    colormap: Option<u32>,
+    visual: xcb::Visualtype,
+    translucent: bool,
+    /// The monitor the window was placed on, kept when the layout changes
+    monitor: leechbar::util::window::Area,
+    layout: Layout,
+    theme: Theme,
+    font: pango::FontDescription,
 }
 
 impl Window {
@@ -21,31 +22,78 @@ impl Window {
         self.id
     }
 
-    pub(crate) fn new(main: &crate::Main, text: &str, class_str: &str) -> Result<Window, Error> {
+    pub(crate) fn new(
+        main: &crate::Main,
+        layout: Layout,
+        class_str: &str,
+    ) -> Result<Window, Error> {
         let conn = main.conn.clone();
         let setup = conn.get_setup();
         let screen = setup.roots().nth(main.screen_num as usize).unwrap();
-        let largest_window = crate::leechbar::util::window::get_largest_window(&conn, &screen)?;
-
-        let (text_width, text_height) =
-            leechbar::component::text::text_size(&text, &main.pango_font).unwrap();
-        let total_width = text_width + (main.border_pad + main.border_size) * 2;
-        let total_height = text_height + (main.border_pad + main.border_size) * 2;
-        let win = conn.generate_id();
-        let (pos_x, pos_y) = if let Some((pos_x, pos_y)) = main.opt.position.split_once(",") {
-            let x = cmdline::parse_position(pos_x, total_width, largest_window.1 .0 as u16)?;
-            let y = cmdline::parse_position(pos_y, total_height, largest_window.1 .1 as u16)?;
-            (
-                (largest_window.0 .0 as i16 + x) as i16,
-                (largest_window.0 .1 as i16 + y) as i16,
+        let monitor = main.monitor()?;
+
+        // Use an ARGB visual when a compositing manager can blend it with what is below the
+        // window, otherwise fall back to opaque drawing on the root visual.
+        let argb_visual =
+            if leechbar::util::window::compositing_manager_running(&conn, main.screen_num)? {
+                leechbar::util::window::find_visualtype(&screen, 32, None)
+            } else {
+                None
+            };
+
+        let (depth, visual, colormap) = if let Some(visual) = argb_visual {
+            let colormap = conn.generate_id();
+            xcb::create_colormap_checked(
+                &conn,
+                xcb::COLORMAP_ALLOC_NONE as u8,
+                colormap,
+                screen.root(),
+                visual.visual_id(),
             )
+            .request_check()?;
+            (32, visual, Some(colormap))
         } else {
-            return Err(Error::InvalidPosition);
+            let visual = leechbar::util::window::find_visualtype(
+                &screen,
+                screen.root_depth(),
+                Some(screen.root_visual()),
+            )
+            .ok_or(Error::NoVisual)?;
+            (xcb::COPY_FROM_PARENT as u8, visual, None)
         };
+        let translucent = colormap.is_some();
+
+        let theme = main.config.theme.clone();
+        let (total_width, total_height) = render::window_size(&layout, &theme, translucent);
+        let win = conn.generate_id();
+        let (pos_x, pos_y) = placement::position(
+            &monitor,
+            main.settings.position(),
+            main.settings.anchor,
+            main.settings.margin(),
+            total_width,
+            total_height,
+        )?;
+
+        let mut values = vec![
+            (xcb::CW_BACK_PIXEL, 0),
+            (xcb::CW_BORDER_PIXEL, 0),
+            (xcb::CW_OVERRIDE_REDIRECT, 1),
+            (
+                xcb::CW_EVENT_MASK,
+                xcb::EVENT_MASK_EXPOSURE
+                    | xcb::EVENT_MASK_STRUCTURE_NOTIFY
+                    | xcb::EVENT_MASK_KEY_PRESS
+                    | xcb::EVENT_MASK_FOCUS_CHANGE,
+            ),
+        ];
+        if let Some(colormap) = colormap {
+            values.push((xcb::CW_COLORMAP, colormap));
+        }
 
         xcb::create_window(
             &conn,
-            xcb::WINDOW_CLASS_COPY_FROM_PARENT as u8,
+            depth,
             win,
             screen.root(),
             pos_x,
@@ -54,18 +102,8 @@ impl Window {
             total_height,
             0,
             xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
-            screen.root_visual(),
-            &[
-                (xcb::CW_BACK_PIXEL, screen.black_pixel()),
-                (xcb::CW_OVERRIDE_REDIRECT, 1),
-                (
-                    xcb::CW_EVENT_MASK,
-                    xcb::EVENT_MASK_EXPOSURE
-                        | xcb::EVENT_MASK_STRUCTURE_NOTIFY
-                        | xcb::EVENT_MASK_KEY_PRESS
-                        | xcb::EVENT_MASK_FOCUS_CHANGE,
-                ),
-            ],
+            visual.visual_id(),
+            &values,
         )
         .request_check()?;
 
@@ -88,75 +126,46 @@ impl Window {
             wm_class,
         );
 
-        let gcontext = leechbar::util::window::create_gc_32(&conn, win)?;
-        let geometry = leechbar::util::Geometry::new(0, 0, text_width, text_height);
-        let color = leechbar::util::Color::new(255, 255, 255, 255);
-        let text = Text::new(
-            conn.clone(),
-            geometry,
-            gcontext,
-            win,
-            main.format32,
-            &text,
-            &main.pango_font,
-            color,
-        )
-        .unwrap();
-        xcb::free_gc_checked(&conn, gcontext);
-
-        let window_pict = conn.generate_id();
-        xcb::render::create_picture_checked(&conn, window_pict, win, main.format24, &[])
-            .request_check()?;
-
         xcb::map_window(&conn, win).request_check()?;
         conn.flush();
 
         let win = Window {
             id: win,
-            frame: main.frame,
-            black: main.black,
-            window_pict,
-            border_size: main.border_size,
-            border_pad: main.border_pad,
-            text_width,
-            text_height,
-            text: text.clone(),
+            colormap,
+            visual,
+            translucent,
+            monitor,
+            layout,
+            theme,
+            font: main.pango_font.clone(),
         };
 
         Ok(win)
     }
 
-    pub(crate) fn update(&mut self, main: &crate::Main, text: &str) -> Result<(), Error> {
+    /// Show a new layout, resizing the window and placing it again on its
+    /// monitor so that it stays centered or anchored.
+    pub(crate) fn update(&mut self, main: &crate::Main, layout: Layout) -> Result<(), Error> {
         let conn = main.conn.clone();
-        let (text_width, text_height) =
-            leechbar::component::text::text_size(&text, &main.pango_font).unwrap();
-        let total_width = text_width + (main.border_pad + main.border_size) * 2;
-        let total_height = text_height + (main.border_pad + main.border_size) * 2;
-
-        let gcontext = leechbar::util::window::create_gc_32(&conn, self.id)?;
-        let geometry = leechbar::util::Geometry::new(0, 0, text_width, text_height);
-        let color = leechbar::util::Color::new(255, 255, 255, 255);
-        let text = Text::new(
-            conn.clone(),
-            geometry,
-            gcontext,
-            self.id,
-            main.format32,
-            &text,
-            &main.pango_font,
-            color,
-        )
-        .unwrap();
-        xcb::free_gc_checked(&conn, gcontext);
+        let (total_width, total_height) =
+            render::window_size(&layout, &self.theme, self.translucent);
+        let (pos_x, pos_y) = placement::position(
+            &self.monitor,
+            main.settings.position(),
+            main.settings.anchor,
+            main.settings.margin(),
+            total_width,
+            total_height,
+        )?;
 
-        self.text_width = text_width;
-        self.text_height = text_height;
-        self.text = text;
+        self.layout = layout;
 
         xcb::configure_window(
             &conn,
             self.id,
             &[
+                (xcb::CONFIG_WINDOW_X as u16, pos_x as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, pos_y as u32),
                 (xcb::CONFIG_WINDOW_WIDTH as u16, total_width as u32),
                 (xcb::CONFIG_WINDOW_HEIGHT as u16, total_height as u32),
             ][..],
@@ -168,25 +177,40 @@ impl Window {
     }
 
     pub(crate) fn draw(&self, conn: &Connection) -> Result<(), Error> {
-        crate::leechbar::util::draw::draw_text_box(
-            &conn,
-            self.id,
-            self.frame,
-            self.black,
-            self.window_pict,
-            self.border_size,
-            self.border_pad,
-            self.text_width,
-            self.text_height,
-            &self.text,
-        )?;
+        let (width, height) = render::window_size(&self.layout, &self.theme, self.translucent);
+        let mut visual = self.visual;
+
+        let surface = unsafe {
+            cairo::Surface::from_raw_full(cairo_sys::cairo_xcb_surface_create(
+                conn.get_raw_conn() as *mut cairo_sys::xcb_connection_t,
+                self.id,
+                (&mut visual.base as *mut xcb::ffi::xcb_visualtype_t)
+                    as *mut cairo_sys::xcb_visualtype_t,
+                i32::from(width),
+                i32::from(height),
+            ))
+        }
+        .map_err(|e| Error::Render(format!("{:?}", e)))?;
+
+        let context = cairo::Context::new(&surface);
+        render::draw(
+            &context,
+            &self.layout,
+            &self.theme,
+            &self.font,
+            self.translucent,
+        );
+        surface.flush();
+        conn.flush();
 
         Ok(())
     }
 
     pub(crate) fn destroy(&self, conn: &Connection) -> Result<(), Error> {
-        xcb::render::free_picture_checked(conn, self.window_pict);
         xcb::destroy_window_checked(conn, self.id);
+        if let Some(colormap) = self.colormap {
+            xcb::free_colormap_checked(conn, colormap);
+        }
         conn.flush();
         Ok(())
     }